@@ -0,0 +1,348 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::{alkane_id_from_bytes, alkane_id_to_bytes, forward_except, RoyaltyNFTCollection};
+
+/// How much each orbital counts for in airdrops and governance votes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HolderWeighting {
+  /// Every orbital counts once
+  Equal,
+  /// Orbitals count in proportion to their rarity score
+  Rarity,
+}
+
+impl HolderWeighting {
+  pub fn from_u128(weighting: u128) -> Result<HolderWeighting> {
+    match weighting {
+      0 => Ok(HolderWeighting::Equal),
+      1 => Ok(HolderWeighting::Rarity),
+      _ => Err(anyhow!("Invalid weighting. Use 0 for equal, 1 for rarity")),
+    }
+  }
+
+  pub fn as_u128(&self) -> u128 {
+    match self {
+      HolderWeighting::Equal => 0,
+      HolderWeighting::Rarity => 1,
+    }
+  }
+}
+
+/// A deposit of partner tokens split across orbitals held at creation time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AirdropCampaign {
+  pub id: u128,
+  pub token: AlkaneId,
+  pub amount: u128,
+  pub weighting: HolderWeighting,
+  /// Orbitals minted before this height are eligible
+  pub snapshot_height: u128,
+  /// Orbital count or total rarity of the eligible orbitals, depending on weighting
+  pub total_weight: u128,
+  pub claimed: u128,
+  /// Claims are accepted below this height; afterwards the owner may sweep the rest
+  pub claim_deadline: u128,
+}
+
+impl AirdropCampaign {
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(144);
+    bytes.extend_from_slice(&self.id.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.token));
+    bytes.extend_from_slice(&self.amount.to_le_bytes());
+    bytes.extend_from_slice(&self.weighting.as_u128().to_le_bytes());
+    bytes.extend_from_slice(&self.snapshot_height.to_le_bytes());
+    bytes.extend_from_slice(&self.total_weight.to_le_bytes());
+    bytes.extend_from_slice(&self.claimed.to_le_bytes());
+    bytes.extend_from_slice(&self.claim_deadline.to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<AirdropCampaign> {
+    if bytes.len() != 144 {
+      return Err(anyhow!("Invalid airdrop data length"));
+    }
+
+    let field = |offset: usize| u128::from_le_bytes(bytes[offset..offset + 16].try_into().unwrap());
+
+    Ok(AirdropCampaign {
+      id: field(0),
+      token: alkane_id_from_bytes(&bytes[16..48])?,
+      amount: field(48),
+      weighting: HolderWeighting::from_u128(field(64))?,
+      snapshot_height: field(80),
+      total_weight: field(96),
+      claimed: field(112),
+      claim_deadline: field(128),
+    })
+  }
+  /// Share of the campaign for an orbital of `weight`, never more than is left
+  fn allocation(&self, weight: u128) -> Result<u128> {
+    let allocation = self.amount.checked_mul(weight)
+      .ok_or_else(|| anyhow!("airdrop amount too large to allocate"))?
+      / self.total_weight;
+
+    Ok(allocation.min(self.amount - self.claimed))
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn airdrop_pointer(&self, campaign_id: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/airdrops/").select(&campaign_id.to_le_bytes().to_vec())
+  }
+
+  fn airdrop_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/airdrops/count")
+  }
+
+  pub(crate) fn airdrop_count(&self) -> u128 {
+    self.airdrop_count_pointer().get_value::<u128>()
+  }
+
+  fn airdrop_claimed_pointer(&self, campaign_id: u128, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/airdrops/claimed/")
+      .select(&campaign_id.to_le_bytes().to_vec())
+      .select(&index.to_le_bytes().to_vec())
+  }
+
+  fn airdrop(&self, campaign_id: u128) -> Result<AirdropCampaign> {
+    let bytes = self.airdrop_pointer(campaign_id).get();
+    if bytes.is_empty() {
+      return Err(anyhow!("Airdrop {} does not exist", campaign_id));
+    }
+
+    AirdropCampaign::from_bytes(&bytes)
+  }
+
+  pub(crate) fn holder_weight(&self, weighting: HolderWeighting, index: u128) -> Result<u128> {
+    match weighting {
+      HolderWeighting::Equal => Ok(1),
+      HolderWeighting::Rarity => self.rarity_score(index),
+    }
+  }
+
+  /// Total weight of unburned orbitals minted before the current block,
+  /// matching the eligibility of a snapshot taken at the current height
+  pub(crate) fn settled_weight(&self, weighting: HolderWeighting) -> u128 {
+    let (count, rarity) = self.current_block_mints();
+
+    match weighting {
+      HolderWeighting::Equal => self.circulating_supply().saturating_sub(count),
+      HolderWeighting::Rarity => self.circulating_rarity().saturating_sub(rarity),
+    }
+  }
+
+  /// Check the call carries the orbital at `index`, or acts for it while
+  /// the collection holds it in custody
+  fn incoming_orbital_or_custody_receipt(&self, index: u128) -> Result<()> {
+    if self.incoming_orbital(index).is_ok() || self.controls_custody_of(index)? {
+      return Ok(());
+    }
+
+    Err(anyhow!("Orbital #{} or the receipt holding it was not supplied", index))
+  }
+
+  /// Amount the orbital at `index` can claim from `campaign`, 0 if ineligible
+  fn airdrop_allocation(&self, campaign: &AirdropCampaign, index: u128) -> Result<u128> {
+    if !self.is_minted(index)
+      || self.mint_height(index) >= campaign.snapshot_height
+      || self.is_burned(index)
+    {
+      return Ok(0);
+    }
+
+    let weight = self.holder_weight(campaign.weighting, index)?;
+
+    campaign.allocation(weight)
+  }
+
+  /// Start an airdrop of the supplied alkane across orbitals minted before
+  /// the current block
+  /// Parameters: [token_block, token_tx, weighting (0=equal, 1=rarity), claim_deadline]
+  /// Returns the campaign id
+  pub(crate) fn create_airdrop(
+    &self,
+    token_block: u128,
+    token_tx: u128,
+    weighting: u128,
+    claim_deadline: u128,
+  ) -> Result<CallResponse> {
+    self.owner_with_alkanes()?;
+
+    let context = self.context()?;
+
+    let token = AlkaneId { block: token_block, tx: token_tx };
+    if token == context.myself {
+      return Err(anyhow!("The collection token cannot be airdropped"));
+    }
+
+    let weighting = HolderWeighting::from_u128(weighting)?;
+    if claim_deadline <= self.height() as u128 {
+      return Err(anyhow!("Airdrop claim deadline must be in the future"));
+    }
+
+    let amount = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.id == token)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+    if amount == 0 {
+      return Err(anyhow!("No airdrop tokens supplied"));
+    }
+
+    let total_weight = self.settled_weight(weighting);
+    if total_weight == 0 {
+      return Err(anyhow!("No orbitals are eligible for an airdrop"));
+    }
+
+    // Campaign ids start at 1
+    let campaign_id = self.airdrop_count_pointer().get_value::<u128>() + 1;
+    self.airdrop_count_pointer().set_value::<u128>(campaign_id);
+
+    let campaign = AirdropCampaign {
+      id: campaign_id,
+      token,
+      amount,
+      weighting,
+      snapshot_height: self.height() as u128,
+      total_weight,
+      claimed: 0,
+      claim_deadline,
+    };
+    self.airdrop_pointer(campaign_id).set(Arc::new(campaign.to_bytes()));
+
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: token, value: amount }])?;
+    response.data = campaign_id.to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  /// Claim an orbital's allocation by presenting it, or whatever holds it in
+  /// custody (see controls_custody_of); whatever was presented is returned
+  pub(crate) fn claim_airdrop(&self, campaign_id: u128, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.incoming_orbital_or_custody_receipt(index)?;
+
+    let mut campaign = self.airdrop(campaign_id)?;
+    if self.height() as u128 >= campaign.claim_deadline {
+      return Err(anyhow!("Claims for airdrop {} closed at height {}", campaign_id, campaign.claim_deadline));
+    }
+
+    if self.airdrop_claimed_pointer(campaign_id, index).get_value::<u128>() != 0 {
+      return Err(anyhow!("Orbital #{} already claimed airdrop {}", index, campaign_id));
+    }
+
+    let allocation = self.airdrop_allocation(&campaign, index)?;
+    if allocation == 0 {
+      return Err(anyhow!("Orbital #{} has no allocation in airdrop {}", index, campaign_id));
+    }
+
+    self.airdrop_claimed_pointer(campaign_id, index).set_value::<u128>(1);
+    campaign.claimed += allocation;
+    self.airdrop_pointer(campaign_id).set(Arc::new(campaign.to_bytes()));
+
+    response.alkanes.0.push(AlkaneTransfer { id: campaign.token, value: allocation });
+
+    Ok(response)
+  }
+
+  /// Return everything left unclaimed once the claim deadline has passed,
+  /// including allocations of orbitals burned or fused since the snapshot
+  pub(crate) fn sweep_airdrop(&self, campaign_id: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let mut campaign = self.airdrop(campaign_id)?;
+    if (self.height() as u128) < campaign.claim_deadline {
+      return Err(anyhow!("Airdrop {} accepts claims until height {}", campaign_id, campaign.claim_deadline));
+    }
+
+    let remaining = campaign.amount - campaign.claimed;
+    if remaining == 0 {
+      return Err(anyhow!("Airdrop {} has nothing left to sweep", campaign_id));
+    }
+
+    campaign.claimed = campaign.amount;
+    self.airdrop_pointer(campaign_id).set(Arc::new(campaign.to_bytes()));
+
+    response.alkanes.0.push(AlkaneTransfer { id: campaign.token, value: remaining });
+
+    Ok(response)
+  }
+
+  /// Returns: [id, token_block, token_tx, amount, weighting, snapshot_height, total_weight, claimed, claim_deadline]
+  pub(crate) fn get_airdrop(&self, campaign_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.airdrop(campaign_id)?.to_bytes();
+
+    Ok(response)
+  }
+
+  /// Unclaimed allocation of the orbital at `index`, 0 once claimed
+  pub(crate) fn get_airdrop_allocation(&self, campaign_id: u128, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let campaign = self.airdrop(campaign_id)?;
+    let allocation = if self.airdrop_claimed_pointer(campaign_id, index).get_value::<u128>() != 0 {
+      0
+    } else {
+      self.airdrop_allocation(&campaign, index)?
+    };
+
+    response.data = allocation.to_le_bytes().to_vec();
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn campaign(amount: u128, total_weight: u128) -> AirdropCampaign {
+    AirdropCampaign {
+      id: 1,
+      token: AlkaneId { block: 2, tx: 9 },
+      amount,
+      weighting: HolderWeighting::Rarity,
+      snapshot_height: 100,
+      total_weight,
+      claimed: 0,
+      claim_deadline: 200,
+    }
+  }
+
+  #[test]
+  fn claims_never_exceed_the_deposit() {
+    let mut campaign = campaign(100, 7);
+
+    let mut paid = Vec::new();
+    for weight in [3, 2, 2] {
+      let allocation = campaign.allocation(weight).unwrap();
+      campaign.claimed += allocation;
+      paid.push(allocation);
+    }
+
+    assert_eq!(paid, vec![42, 28, 28]);
+    assert!(campaign.claimed <= campaign.amount);
+  }
+
+  #[test]
+  fn allocation_is_capped_by_what_is_left() {
+    let mut campaign = campaign(10, 2);
+    campaign.claimed = 8;
+
+    assert_eq!(campaign.allocation(1).unwrap(), 2);
+  }
+}
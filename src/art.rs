@@ -0,0 +1,65 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::response::CallResponse;
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::svg_generator::{StyleWeights, ART_STYLE_COUNT};
+use crate::RoyaltyNFTCollection;
+
+impl RoyaltyNFTCollection {
+  fn style_weights_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/art/style-weights")
+  }
+
+  /// Art style weights index-derived orbitals are drawn with
+  pub(crate) fn style_weights(&self) -> Result<StyleWeights> {
+    let bytes = self.style_weights_pointer().get();
+    if bytes.is_empty() {
+      return Ok(StyleWeights::default());
+    }
+
+    StyleWeights::from_bytes(&bytes)
+  }
+
+  /// Set how often an art style is drawn. Styles: 0=fractal, 1=flow field,
+  /// 2=circle packing, 3=mandala, 4=wave, 5=crystalline, 6=voronoi, 7=truchet,
+  /// 8=l-system, 9=noise contours. Art is derived from the weights, so they
+  /// can only change before minting starts.
+  /// Parameters: [style, weight]
+  pub(crate) fn set_style_weight(&self, style: u128, weight: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if style >= ART_STYLE_COUNT as u128 {
+      return Err(anyhow!("Art style must be below {}", ART_STYLE_COUNT));
+    }
+    if self.instances_count() > 0 {
+      return Err(anyhow!("Style weights cannot change once minting has started"));
+    }
+
+    let mut weights = self.style_weights()?;
+    weights.0[style as usize] = weight;
+    if weights.total() == 0 {
+      return Err(anyhow!("At least one art style must have a weight"));
+    }
+
+    self.style_weights_pointer().set(Arc::new(weights.to_bytes()));
+
+    Ok(response)
+  }
+
+  /// Returns: [weight for each art style in style order]
+  pub(crate) fn get_style_weights(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.style_weights()?.to_bytes();
+
+    Ok(response)
+  }
+}
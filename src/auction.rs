@@ -0,0 +1,424 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::receipt::ReceiptKind;
+use crate::refund::GuaranteeState;
+use crate::storage_set::StorageSet;
+use crate::{
+  alkane_id_from_bytes, alkane_id_to_bytes, forward_except, PaymentToken, RoyaltyNFTCollection,
+};
+
+/// Maximum number of auctions returned by a single GetAuctions call
+const MAX_AUCTIONS_PAGE: u128 = 50;
+
+/// How the winning bid of an ended auction is accounted for
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BidSettlement {
+  /// Sale of an escrowed orbital: the bid goes to the treasury
+  Treasury,
+  /// Primary sale of a reserved index, settled like any paid mint: escrowed
+  /// while a refund guarantee is active, otherwise credited to the treasury
+  Mint,
+  /// Minting closed after a failed guarantee: the bid is returned to the bidder
+  Refund,
+}
+
+/// An English auction for a single orbital index
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Auction {
+  pub index: u128,
+  /// Whether the orbital already existed and is escrowed, rather than minted on settlement
+  pub held: bool,
+  /// Account an unsold held orbital is returned to: the collection itself,
+  /// claimed with its auth token
+  pub seller: AlkaneId,
+  pub payment_token: PaymentToken,
+  pub reserve_price: u128,
+  pub end_height: u128,
+  /// Bid receipt of the highest bidder
+  pub highest_bidder: AlkaneId,
+  pub highest_bid: u128,
+}
+
+impl Auction {
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(176);
+    bytes.extend_from_slice(&self.index.to_le_bytes());
+    bytes.extend_from_slice(&(self.held as u128).to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.seller));
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.payment_token.get_id()));
+    bytes.extend_from_slice(&self.reserve_price.to_le_bytes());
+    bytes.extend_from_slice(&self.end_height.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.highest_bidder));
+    bytes.extend_from_slice(&self.highest_bid.to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Auction> {
+    if bytes.len() != 176 {
+      return Err(anyhow!("Invalid auction data length"));
+    }
+
+    let token_id = alkane_id_from_bytes(&bytes[64..96])?;
+
+    Ok(Auction {
+      index: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+      held: u128::from_le_bytes(bytes[16..32].try_into().unwrap()) != 0,
+      seller: alkane_id_from_bytes(&bytes[32..64])?,
+      payment_token: PaymentToken::from_alkane_id(&token_id)
+        .ok_or_else(|| anyhow!("auction uses an unsupported payment token"))?,
+      reserve_price: u128::from_le_bytes(bytes[96..112].try_into().unwrap()),
+      end_height: u128::from_le_bytes(bytes[112..128].try_into().unwrap()),
+      highest_bidder: alkane_id_from_bytes(&bytes[128..160])?,
+      highest_bid: u128::from_le_bytes(bytes[160..176].try_into().unwrap()),
+    })
+  }
+
+  /// A bid must meet the reserve price and beat the current highest bid
+  fn check_bid(&self, bid: u128) -> Result<()> {
+    if bid < self.reserve_price || bid == 0 {
+      return Err(anyhow!("Bid of {} is below the reserve price of {}", bid, self.reserve_price));
+    }
+
+    if bid <= self.highest_bid {
+      return Err(anyhow!("Bid of {} does not beat the highest bid of {}", bid, self.highest_bid));
+    }
+
+    Ok(())
+  }
+
+  fn bid_settlement(&self, guarantee: GuaranteeState) -> BidSettlement {
+    if self.held {
+      BidSettlement::Treasury
+    } else if guarantee == GuaranteeState::Failed {
+      BidSettlement::Refund
+    } else {
+      BidSettlement::Mint
+    }
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn auction_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/auctions/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn active_auctions(&self) -> StorageSet {
+    StorageSet::new("/auctions/active")
+  }
+
+  /// Indices behind the mint cursor whose reservation ended unsold; regular
+  /// mints take these before advancing the cursor
+  pub(crate) fn released_indices(&self) -> StorageSet {
+    StorageSet::new("/auctions/released")
+  }
+
+  fn reserved_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/auctions/reserved/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn reserved_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/auctions/reserved-count")
+  }
+
+  /// Whether an unminted index is held back from regular mints for auction
+  pub(crate) fn is_reserved(&self, index: u128) -> bool {
+    self.reserved_pointer(index).get_value::<u128>() != 0
+  }
+
+  /// Unminted indices currently held back for auction
+  pub(crate) fn reserved_count(&self) -> u128 {
+    self.reserved_count_pointer().get_value::<u128>()
+  }
+
+  fn set_reserved(&self, index: u128, reserved: bool) {
+    if self.is_reserved(index) == reserved {
+      return;
+    }
+
+    let count = self.reserved_count();
+    self.reserved_count_pointer().set_value::<u128>(if reserved { count + 1 } else { count.saturating_sub(1) });
+    self.reserved_pointer(index).set_value::<u128>(reserved as u128);
+  }
+
+  pub(crate) fn auction(&self, index: u128) -> Result<Option<Auction>> {
+    let bytes = self.auction_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(Auction::from_bytes(&bytes)?))
+  }
+
+  fn save_auction(&self, auction: &Auction) {
+    self.auction_pointer(auction.index).set(Arc::new(auction.to_bytes()));
+  }
+
+  fn close_auction(&self, index: u128) {
+    self.active_auctions().remove(&index.to_le_bytes());
+    self.auction_pointer(index).set(Arc::new(Vec::new()));
+    self.set_reserved(index, false);
+  }
+
+  /// Auction an unminted regular index, or any minted orbital supplied with the call
+  /// Parameters: [index, token_type (0=frBTC, 1=BUSD), reserve_price, end_height]
+  pub(crate) fn start_auction(
+    &self,
+    index: u128,
+    token_type: u128,
+    reserve_price: u128,
+    end_height: u128,
+  ) -> Result<CallResponse> {
+    self.owner_with_alkanes()?;
+
+    let context = self.context()?;
+
+    let payment_token = PaymentToken::from_token_type(token_type)?;
+
+    // Any minted orbital can be auctioned from escrow, fused ones included;
+    // only regular indices can be reserved before they are minted
+    let held = self.is_minted(index);
+    if !held && index >= self.max_mints() {
+      return Err(anyhow!("Orbital index {} is outside the collection", index));
+    }
+
+    if end_height <= self.height() as u128 {
+      return Err(anyhow!("Auction end height must be in the future"));
+    }
+
+    if self.auction(index)?.is_some() {
+      return Err(anyhow!("Orbital #{} is already being auctioned", index));
+    }
+
+    let response = if held {
+      let orbital_id = self.incoming_orbital(index)?;
+      forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: orbital_id, value: 1 }])?
+    } else {
+      self.ensure_minting_open()?;
+      if self.open_supply() == 0 {
+        return Err(anyhow!("No unminted orbitals remain outside outstanding mint passes"));
+      }
+      self.set_reserved(index, true);
+      self.released_indices().remove(&index.to_le_bytes());
+      CallResponse::forward(&context.incoming_alkanes)
+    };
+
+    let auction = Auction {
+      index,
+      held,
+      seller: context.myself.clone(),
+      payment_token,
+      reserve_price,
+      end_height,
+      highest_bidder: AlkaneId { block: 0, tx: 0 },
+      highest_bid: 0,
+    };
+    self.save_auction(&auction);
+    self.active_auctions().insert(&index.to_le_bytes());
+
+    Ok(response)
+  }
+
+  /// Bid the supplied amount of the auction token, receiving a bid receipt.
+  /// The previous highest bid is returned in the response when its receipt
+  /// is supplied, as when a bidder raises their own bid; otherwise it is
+  /// credited to that receipt's claimable balance (ClaimSaleProceeds).
+  pub(crate) fn place_bid(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let mut auction = self.auction(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} is not being auctioned", index))?;
+
+    if self.height() as u128 >= auction.end_height {
+      return Err(anyhow!("Auction for orbital #{} has ended", index));
+    }
+    if !auction.held {
+      self.ensure_minting_open()?;
+    }
+
+    let token = auction.payment_token.get_id();
+    let bid = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.id == token)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    auction.check_bid(bid)?;
+
+    let mut kept = vec![AlkaneTransfer { id: token, value: bid }];
+    let mut refund = None;
+    if auction.highest_bid > 0 {
+      let previous_receipt_supplied = context.incoming_alkanes.0.iter()
+        .any(|transfer| transfer.id == auction.highest_bidder && transfer.value > 0);
+
+      if previous_receipt_supplied {
+        kept.push(AlkaneTransfer { id: auction.highest_bidder.clone(), value: 1 });
+        refund = Some(AlkaneTransfer { id: token, value: auction.highest_bid });
+      } else {
+        self.credit_sale_proceeds(&auction.highest_bidder, &token, auction.highest_bid)?;
+      }
+    }
+
+    let mut response = forward_except(&context.incoming_alkanes, &kept)?;
+
+    let receipt = self.issue_receipt(ReceiptKind::Bid)?;
+    auction.highest_bidder = receipt.id;
+    auction.highest_bid = bid;
+    self.save_auction(&auction);
+
+    response.alkanes.0.push(receipt);
+    response.alkanes.0.extend(refund);
+
+    Ok(response)
+  }
+
+  /// Close an ended auction: the winning bid goes to the treasury and the
+  /// orbital to the winner. A reserved index is minted first and its bid is
+  /// settled like any paid mint, or refunded to the bidder if minting closed
+  /// after a failed guarantee. Callable by anyone; the orbital is returned
+  /// directly when the winning bid receipt is supplied and otherwise held for
+  /// it (ClaimOrbital).
+  pub(crate) fn settle_auction(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let auction = self.auction(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} is not being auctioned", index))?;
+
+    if (self.height() as u128) < auction.end_height {
+      return Err(anyhow!("Auction for orbital #{} ends at height {}", index, auction.end_height));
+    }
+
+    self.close_auction(index);
+
+    let token = auction.payment_token.get_id();
+    let recipient = if auction.highest_bid > 0 {
+      match auction.bid_settlement(self.guarantee_state()) {
+        BidSettlement::Treasury => self.credit_treasury(&token, auction.highest_bid)?,
+        BidSettlement::Mint => {
+          let payment = AlkaneTransfer { id: token, value: auction.highest_bid };
+          self.settle_mint_payment(index, &payment, None)?;
+          self.mint_orbital_at(index)?;
+        }
+        BidSettlement::Refund => {
+          self.credit_sale_proceeds(&auction.highest_bidder, &token, auction.highest_bid)?;
+          return Ok(CallResponse::forward(&context.incoming_alkanes));
+        }
+      }
+
+      auction.highest_bidder
+    } else if auction.held {
+      auction.seller
+    } else {
+      // An unsold reserved index the mint cursor has passed goes back to
+      // regular mints through the released list
+      if index < self.mint_cursor() {
+        self.released_indices().insert(&index.to_le_bytes());
+      }
+      return Ok(CallResponse::forward(&context.incoming_alkanes));
+    };
+
+    match self.take_receipt(&recipient) {
+      Ok(kept) => {
+        let mut response = forward_except(&context.incoming_alkanes, &kept)?;
+        response.alkanes.0.push(AlkaneTransfer {
+          id: self.lookup_instance(index)?,
+          value: 1,
+        });
+        Ok(response)
+      }
+      Err(_) => {
+        self.hold_orbital_for(index, &recipient);
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+      }
+    }
+  }
+
+  /// Returns the auction record:
+  /// [index, held, seller_block, seller_tx, token_block, token_tx, reserve_price,
+  ///  end_height, bidder_block, bidder_tx, highest_bid]
+  pub(crate) fn get_auction(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let auction = self.auction(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} is not being auctioned", index))?;
+
+    response.data = auction.to_bytes();
+    Ok(response)
+  }
+
+  /// Returns: [total, auction records as in GetAuction...]
+  pub(crate) fn get_auctions(&self, offset: u128, limit: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let indices = self.active_auctions();
+    let page = indices.page(offset, limit.min(MAX_AUCTIONS_PAGE));
+
+    let mut data = Vec::with_capacity(16 + page.len() * 176);
+    data.extend_from_slice(&indices.len().to_le_bytes());
+    for key in page {
+      let index = u128::from_le_bytes(key[..16].try_into().unwrap());
+      if let Some(auction) = self.auction(index)? {
+        data.extend_from_slice(&auction.to_bytes());
+      }
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn auction(reserve_price: u128, highest_bid: u128) -> Auction {
+    Auction {
+      index: 3300,
+      held: false,
+      seller: AlkaneId { block: 2, tx: 1 },
+      payment_token: PaymentToken::FrBTC,
+      reserve_price,
+      end_height: 880_000,
+      highest_bidder: AlkaneId { block: 2, tx: 777 },
+      highest_bid,
+    }
+  }
+
+  #[test]
+  fn first_bid_must_meet_reserve() {
+    assert!(auction(50_000, 0).check_bid(49_999).is_err());
+    assert!(auction(50_000, 0).check_bid(50_000).is_ok());
+    assert!(auction(0, 0).check_bid(0).is_err());
+  }
+
+  #[test]
+  fn later_bids_must_beat_the_highest() {
+    assert!(auction(50_000, 64_000).check_bid(64_000).is_err());
+    assert!(auction(50_000, 64_000).check_bid(64_001).is_ok());
+  }
+
+  #[test]
+  fn reserved_index_under_active_guarantee_settles_as_a_mint() {
+    assert_eq!(auction(50_000, 64_000).bid_settlement(GuaranteeState::Active), BidSettlement::Mint);
+    assert_eq!(auction(50_000, 64_000).bid_settlement(GuaranteeState::None), BidSettlement::Mint);
+  }
+
+  #[test]
+  fn reserved_index_bid_is_refunded_once_minting_closes() {
+    assert_eq!(auction(50_000, 64_000).bid_settlement(GuaranteeState::Failed), BidSettlement::Refund);
+  }
+
+  #[test]
+  fn held_orbital_bid_always_goes_to_the_treasury() {
+    let held = Auction { held: true, ..auction(50_000, 64_000) };
+
+    assert_eq!(held.bid_settlement(GuaranteeState::Active), BidSettlement::Treasury);
+    assert_eq!(held.bid_settlement(GuaranteeState::Failed), BidSettlement::Treasury);
+  }
+}
@@ -0,0 +1,253 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{
+  cellpack::Cellpack, id::AlkaneId, parcel::{AlkaneTransfer, AlkaneTransferParcel},
+  response::CallResponse,
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::svg_generator::SvgGenerator;
+use crate::{
+  alkane_id_from_bytes, alkane_id_to_bytes, forward_except, RoyaltyNFTCollection,
+  BUNDLE_RECEIPT_TEMPLATE_ID,
+};
+
+/// Maximum number of orbitals wrapped into a single bundle
+const MAX_BUNDLE_SIZE: usize = 12;
+
+/// Orbitals escrowed together behind a single receipt alkane
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bundle {
+  pub id: u128,
+  pub receipt_id: AlkaneId,
+  pub unwrapped: bool,
+  pub indices: Vec<u128>,
+}
+
+impl Bundle {
+  fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(80 + self.indices.len() * 16);
+    bytes.extend_from_slice(&self.id.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.receipt_id));
+    bytes.extend_from_slice(&(self.unwrapped as u128).to_le_bytes());
+    bytes.extend_from_slice(&(self.indices.len() as u128).to_le_bytes());
+    for index in &self.indices {
+      bytes.extend_from_slice(&index.to_le_bytes());
+    }
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Bundle> {
+    if bytes.len() < 80 {
+      return Err(anyhow!("Invalid bundle data length"));
+    }
+
+    let count = u128::from_le_bytes(bytes[64..80].try_into().unwrap()) as usize;
+    if bytes.len() != 80 + count * 16 {
+      return Err(anyhow!("Invalid bundle data length"));
+    }
+
+    Ok(Bundle {
+      id: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+      receipt_id: alkane_id_from_bytes(&bytes[16..48])?,
+      unwrapped: u128::from_le_bytes(bytes[48..64].try_into().unwrap()) != 0,
+      indices: bytes[80..]
+        .chunks(16)
+        .map(|chunk| u128::from_le_bytes(chunk.try_into().unwrap()))
+        .collect(),
+    })
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn bundle_pointer(&self, bundle_id: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/bundles/").select(&bundle_id.to_le_bytes().to_vec())
+  }
+
+  /// Open bundle wrapping the orbital at `index`, 0 if none
+  fn orbital_bundle_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/bundles/orbitals/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn bundle_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/bundle-count")
+  }
+
+  fn bundle(&self, bundle_id: u128) -> Result<Bundle> {
+    let bytes = self.bundle_pointer(bundle_id).get();
+    if bytes.is_empty() {
+      return Err(anyhow!("Bundle {} does not exist", bundle_id));
+    }
+
+    Bundle::from_bytes(&bytes)
+  }
+
+  /// Receipt of the open bundle wrapping the orbital at `index`, if any
+  pub(crate) fn bundle_receipt_for(&self, index: u128) -> Result<Option<AlkaneId>> {
+    let bundle_id = self.orbital_bundle_pointer(index).get_value::<u128>();
+    if bundle_id == 0 {
+      return Ok(None);
+    }
+
+    Ok(Some(self.bundle(bundle_id)?.receipt_id))
+  }
+
+  /// Deploy the receipt alkane representing a bundle
+  fn deploy_bundle_receipt(&self, bundle_id: u128) -> Result<AlkaneTransfer> {
+    let cellpack = Cellpack {
+      target: AlkaneId {
+        block: 6,
+        tx: BUNDLE_RECEIPT_TEMPLATE_ID,
+      },
+      inputs: vec![0x0, bundle_id],
+    };
+
+    let sequence = self.sequence();
+    let response = self.call(&cellpack, &AlkaneTransferParcel::default(), self.fuel())?;
+
+    let receipt_id = AlkaneId {
+      block: 2,
+      tx: sequence,
+    };
+
+    let minted = response.alkanes.0.iter()
+      .filter(|transfer| transfer.id == receipt_id)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    // Anything beyond the single receipt would be stranded in the collection
+    if minted != 1 {
+      return Err(anyhow!("bundle receipt template returned {} units instead of 1", minted));
+    }
+
+    Ok(AlkaneTransfer { id: receipt_id, value: 1 })
+  }
+
+  /// Escrow every supplied orbital and mint a receipt alkane for the set
+  /// Returns the bundle id
+  pub(crate) fn wrap_bundle(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let indices = self.incoming_orbital_indices()?;
+    if indices.len() < 2 || indices.len() > MAX_BUNDLE_SIZE {
+      return Err(anyhow!("A bundle holds between 2 and {} orbitals, {} supplied", MAX_BUNDLE_SIZE, indices.len()));
+    }
+
+    let mut escrowed = Vec::with_capacity(indices.len());
+    for index in &indices {
+      escrowed.push(AlkaneTransfer { id: self.lookup_instance(*index)?, value: 1 });
+    }
+
+    // The orbitals stay in escrow until the receipt is returned
+    let mut response = forward_except(&context.incoming_alkanes, &escrowed)?;
+
+    // Bundle ids start at 1
+    let bundle_id = self.bundle_count_pointer().get_value::<u128>() + 1;
+    self.bundle_count_pointer().set_value::<u128>(bundle_id);
+
+    let receipt = self.deploy_bundle_receipt(bundle_id)?;
+
+    let bundle = Bundle {
+      id: bundle_id,
+      receipt_id: receipt.id,
+      unwrapped: false,
+      indices,
+    };
+    self.bundle_pointer(bundle_id).set(Arc::new(bundle.to_bytes()));
+    for index in &bundle.indices {
+      self.orbital_bundle_pointer(*index).set_value::<u128>(bundle_id);
+    }
+
+    response.alkanes.0.push(receipt);
+    response.data = bundle_id.to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  /// Return a bundle receipt and receive the orbitals it holds
+  pub(crate) fn unwrap_bundle(&self, bundle_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let mut bundle = self.bundle(bundle_id)?;
+    if bundle.unwrapped {
+      return Err(anyhow!("Bundle {} was already unwrapped", bundle_id));
+    }
+
+    // The receipt is retired with the bundle
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: bundle.receipt_id, value: 1 }])?;
+
+    bundle.unwrapped = true;
+    self.bundle_pointer(bundle_id).set(Arc::new(bundle.to_bytes()));
+
+    for index in &bundle.indices {
+      self.orbital_bundle_pointer(*index).set_value::<u128>(0);
+      response.alkanes.0.push(AlkaneTransfer {
+        id: self.lookup_instance(*index)?,
+        value: 1,
+      });
+    }
+
+    Ok(response)
+  }
+
+  /// Returns: [id, receipt_block, receipt_tx, unwrapped, count, index...]
+  pub(crate) fn get_bundle(&self, bundle_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.bundle(bundle_id)?.to_bytes();
+
+    Ok(response)
+  }
+
+  /// Called by bundle receipts - attributes of every contained orbital,
+  /// including the lineage of fused ones
+  pub(crate) fn get_bundle_attributes(&self, bundle_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let bundle = self.bundle(bundle_id)?;
+
+    let mut pieces = Vec::with_capacity(bundle.indices.len());
+    let mut total_rarity = 0u128;
+    for index in &bundle.indices {
+      let traits = self.orbital_traits(*index)?;
+      total_rarity += SvgGenerator::calculate_rarity_score(&traits);
+      pieces.push(json!({
+        "index": index,
+        "attributes": self.orbital_attributes(*index)?
+      }));
+    }
+
+    let attributes = json!({
+      "bundle_id": bundle_id,
+      "size": bundle.indices.len(),
+      "total_rarity_score": total_rarity,
+      "pieces": pieces
+    });
+
+    response.data = attributes.to_string().into_bytes();
+    Ok(response)
+  }
+
+  /// Called by bundle receipts - composite SVG preview of the contained
+  /// orbitals, each rendered from its stored traits
+  pub(crate) fn get_bundle_data(&self, bundle_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let bundle = self.bundle(bundle_id)?;
+
+    let mut pieces = Vec::with_capacity(bundle.indices.len());
+    for index in &bundle.indices {
+      pieces.push((*index, self.orbital_traits(*index)?));
+    }
+
+    response.data = SvgGenerator::generate_bundle_svg(bundle_id, &pieces)?.into_bytes();
+    Ok(response)
+  }
+}
@@ -0,0 +1,193 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::{alkane_id_from_bytes, alkane_id_to_bytes, forward_except, RoyaltyNFTCollection};
+
+/// Alkane released to holders who burn an orbital
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BurnRedemption {
+  pub token: AlkaneId,
+  pub amount: u128,
+}
+
+impl RoyaltyNFTCollection {
+  fn burned_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/burned/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn burned_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/burned-count")
+  }
+
+  fn burn_redemption_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/burn/redemption")
+  }
+
+  fn burn_pool_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/burn/pool/").select(&alkane_id_to_bytes(token))
+  }
+
+  /// Redemption payouts held for future burns in `token`
+  pub(crate) fn burn_pool(&self, token: &AlkaneId) -> u128 {
+    self.burn_pool_pointer(token).get_value::<u128>()
+  }
+
+  pub(crate) fn is_burned(&self, index: u128) -> bool {
+    self.burned_pointer(index).get_value::<u128>() != 0
+  }
+
+  pub(crate) fn burned_count(&self) -> u128 {
+    self.burned_count_pointer().get_value::<u128>()
+  }
+
+  /// Minted orbitals that have not been burned
+  pub(crate) fn circulating_supply(&self) -> u128 {
+    self.instances_count() - self.burned_count()
+  }
+
+  fn burn_redemption(&self) -> Result<Option<BurnRedemption>> {
+    let bytes = self.burn_redemption_pointer().get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+    if bytes.len() != 48 {
+      return Err(anyhow!("Invalid burn redemption data length"));
+    }
+
+    let redemption = BurnRedemption {
+      token: alkane_id_from_bytes(&bytes[..32])?,
+      amount: u128::from_le_bytes(bytes[32..48].try_into().unwrap()),
+    };
+
+    Ok(if redemption.amount == 0 { None } else { Some(redemption) })
+  }
+
+  /// Retire the orbital at `index`, which must already be held by the
+  /// collection, and update burn accounting
+  pub(crate) fn retire_orbital(&self, index: u128) -> Result<()> {
+    if self.is_burned(index) {
+      return Err(anyhow!("Orbital #{} is already burned", index));
+    }
+
+    self.track_voting_weight(Some(index))?;
+    self.burned_pointer(index).set_value::<u128>(1);
+    self.burned_count_pointer().set_value::<u128>(self.burned_count() + 1);
+    self.adjust_circulating(index, false)?;
+
+    Ok(())
+  }
+
+  /// Burn the supplied orbital, releasing the configured redemption if any
+  pub(crate) fn burn(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let orbital_id = self.incoming_orbital(index)?;
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: orbital_id, value: 1 }])?;
+
+    // The collection keeps the orbital; it can never leave again
+    self.retire_orbital(index)?;
+
+    if let Some(redemption) = self.burn_redemption()? {
+      let pool = self.burn_pool_pointer(&redemption.token).get_value::<u128>();
+      if pool < redemption.amount {
+        return Err(anyhow!(
+          "Burn redemption pool exhausted: {} required, {} available",
+          redemption.amount, pool
+        ));
+      }
+
+      self.burn_pool_pointer(&redemption.token).set_value::<u128>(pool - redemption.amount);
+      response.alkanes.0.push(AlkaneTransfer {
+        id: redemption.token,
+        value: redemption.amount,
+      });
+    }
+
+    Ok(response)
+  }
+
+  /// Configure the alkane paid out per burn; an amount of 0 disables redemption
+  /// Parameters: [token_block, token_tx, amount]
+  pub(crate) fn set_burn_redemption(&self, token_block: u128, token_tx: u128, amount: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    let token = AlkaneId { block: token_block, tx: token_tx };
+
+    let mut bytes = alkane_id_to_bytes(&token);
+    bytes.extend_from_slice(&amount.to_le_bytes());
+    self.burn_redemption_pointer().set(Arc::new(bytes));
+
+    Ok(response)
+  }
+
+  /// Deposit the configured redemption alkane into the burn pool
+  pub(crate) fn fund_burn_redemption(&self) -> Result<CallResponse> {
+    self.owner_with_alkanes()?;
+
+    let context = self.context()?;
+
+    let redemption = self.burn_redemption()?
+      .ok_or_else(|| anyhow!("No burn redemption is configured"))?;
+
+    let deposit = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.id == redemption.token)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    if deposit == 0 {
+      return Err(anyhow!("No redemption alkanes supplied"));
+    }
+
+    let pool = self.burn_pool_pointer(&redemption.token).get_value::<u128>()
+      .checked_add(deposit)
+      .ok_or_else(|| anyhow!("burn pool overflow"))?;
+    self.burn_pool_pointer(&redemption.token).set_value::<u128>(pool);
+
+    forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: redemption.token, value: deposit }])
+  }
+
+  pub(crate) fn get_burn_status(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = (self.is_burned(index) as u128).to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  pub(crate) fn get_circulating_supply(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.circulating_supply().to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  /// Returns: [token_block, token_tx, amount_per_burn, pool_balance]
+  pub(crate) fn get_burn_redemption(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let mut data = Vec::with_capacity(64);
+    match self.burn_redemption()? {
+      Some(redemption) => {
+        data.extend_from_slice(&alkane_id_to_bytes(&redemption.token));
+        data.extend_from_slice(&redemption.amount.to_le_bytes());
+        data.extend_from_slice(&self.burn_pool_pointer(&redemption.token).get_value::<u128>().to_le_bytes());
+      }
+      None => data.resize(64, 0),
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+}
@@ -0,0 +1,211 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::storage_set::StorageSet;
+use crate::{
+  alkane_id_from_bytes, alkane_id_to_bytes, PaymentToken, RoyaltyNFTCollection, BPS_DENOMINATOR,
+};
+
+/// Mint price reduction granted to callers presenting a partner alkane
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Discount {
+  pub id: AlkaneId,
+  pub discount_bps: u128,
+  /// Maximum discounted mints shared by every holder of the alkane, 0 for unlimited
+  pub usage_cap: u128,
+  pub used: u128,
+}
+
+impl Discount {
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(80);
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.id));
+    bytes.extend_from_slice(&self.discount_bps.to_le_bytes());
+    bytes.extend_from_slice(&self.usage_cap.to_le_bytes());
+    bytes.extend_from_slice(&self.used.to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Discount> {
+    if bytes.len() != 80 {
+      return Err(anyhow!("Invalid discount data length"));
+    }
+
+    Ok(Discount {
+      id: alkane_id_from_bytes(&bytes[..32])?,
+      discount_bps: u128::from_le_bytes(bytes[32..48].try_into().unwrap()),
+      usage_cap: u128::from_le_bytes(bytes[48..64].try_into().unwrap()),
+      used: u128::from_le_bytes(bytes[64..80].try_into().unwrap()),
+    })
+  }
+
+  /// Discounted mints still available
+  pub fn remaining_uses(&self) -> u128 {
+    if self.usage_cap == 0 {
+      u128::MAX
+    } else {
+      self.usage_cap.saturating_sub(self.used)
+    }
+  }
+
+  /// Price per mint after the discount, never below one unit
+  pub fn apply(&self, price: u128) -> u128 {
+    (price * (BPS_DENOMINATOR - self.discount_bps) / BPS_DENOMINATOR).max(1)
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn discount_pointer(&self, id: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/discounts/").select(&alkane_id_to_bytes(id))
+  }
+
+  fn discount_ids(&self) -> StorageSet {
+    StorageSet::new("/discount-ids")
+  }
+
+  fn discount(&self, id: &AlkaneId) -> Result<Option<Discount>> {
+    if !self.discount_ids().contains(&alkane_id_to_bytes(id)) {
+      return Ok(None);
+    }
+
+    Ok(Some(Discount::from_bytes(&self.discount_pointer(id).get())?))
+  }
+
+  /// Best registered discount among the alkanes supplied with the call that
+  /// still has uses left. The discount alkane itself is returned to the caller.
+  pub(crate) fn presented_discount(&self) -> Result<Option<Discount>> {
+    let context = self.context()?;
+
+    let mut best: Option<Discount> = None;
+    for transfer in &context.incoming_alkanes.0 {
+      if transfer.value == 0 {
+        continue;
+      }
+
+      if let Some(discount) = self.discount(&transfer.id)? {
+        if discount.remaining_uses() > 0
+          && best.map_or(true, |best| discount.discount_bps > best.discount_bps)
+        {
+          best = Some(discount);
+        }
+      }
+    }
+
+    Ok(best)
+  }
+
+  /// Record `count` discounted mints against a discount's usage cap
+  pub(crate) fn use_discount(&self, discount: &Discount, count: u128) -> Result<()> {
+    let mut discount = self.discount(&discount.id)?
+      .ok_or_else(|| anyhow!("Discount {}:{} is not registered", discount.id.block, discount.id.tx))?;
+
+    if count > discount.remaining_uses() {
+      return Err(anyhow!("Discount {}:{} has {} uses left", discount.id.block, discount.id.tx, discount.remaining_uses()));
+    }
+
+    discount.used += count;
+    self.discount_pointer(&discount.id).set(Arc::new(discount.to_bytes()));
+
+    Ok(())
+  }
+
+  /// Register or update a discount for holders of an alkane. Updating keeps the usage count.
+  /// Parameters: [token_block, token_tx, discount_bps, usage_cap (0=unlimited)]
+  pub(crate) fn set_discount(
+    &self,
+    token_block: u128,
+    token_tx: u128,
+    discount_bps: u128,
+    usage_cap: u128,
+  ) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if discount_bps == 0 || discount_bps >= BPS_DENOMINATOR {
+      return Err(anyhow!("Discount must be between 1 and {} bps", BPS_DENOMINATOR - 1));
+    }
+
+    let id = AlkaneId { block: token_block, tx: token_tx };
+    if id == context.myself || PaymentToken::from_alkane_id(&id).is_some() {
+      return Err(anyhow!("Payment tokens and the collection token cannot be discount tokens"));
+    }
+
+    let used = self.discount(&id)?.map(|discount| discount.used).unwrap_or(0);
+    let discount = Discount { id, discount_bps, usage_cap, used };
+    self.discount_pointer(&id).set(Arc::new(discount.to_bytes()));
+    self.discount_ids().insert(&alkane_id_to_bytes(&id));
+
+    Ok(response)
+  }
+
+  pub(crate) fn remove_discount(&self, token_block: u128, token_tx: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    let id = AlkaneId { block: token_block, tx: token_tx };
+    if !self.discount_ids().contains(&alkane_id_to_bytes(&id)) {
+      return Err(anyhow!("Discount {}:{} is not registered", id.block, id.tx));
+    }
+
+    self.discount_ids().remove(&alkane_id_to_bytes(&id));
+    self.discount_pointer(&id).set(Arc::new(Vec::new()));
+
+    Ok(response)
+  }
+
+  /// Returns: [count, (token_block, token_tx, discount_bps, usage_cap, used)...]
+  pub(crate) fn get_discounts(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let ids = self.discount_ids();
+    let count = ids.len();
+
+    let mut data = Vec::with_capacity(16 + count as usize * 80);
+    data.extend_from_slice(&count.to_le_bytes());
+    for key in ids.page(0, count) {
+      if let Some(discount) = self.discount(&alkane_id_from_bytes(&key)?)? {
+        data.extend_from_slice(&discount.to_bytes());
+      }
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn discount(discount_bps: u128, usage_cap: u128, used: u128) -> Discount {
+    Discount { id: AlkaneId { block: 2, tx: 50 }, discount_bps, usage_cap, used }
+  }
+
+  #[test]
+  fn apply_reduces_price_by_bps() {
+    assert_eq!(discount(2500, 0, 0).apply(10_000), 7_500);
+    assert_eq!(discount(1, 0, 0).apply(10_000), 9_999);
+  }
+
+  #[test]
+  fn apply_never_goes_below_one_unit() {
+    assert_eq!(discount(9_999, 0, 0).apply(3), 1);
+  }
+
+  #[test]
+  fn remaining_uses_counts_down_to_cap() {
+    assert_eq!(discount(2500, 0, 40).remaining_uses(), u128::MAX);
+    assert_eq!(discount(2500, 5, 3).remaining_uses(), 2);
+    assert_eq!(discount(2500, 5, 7).remaining_uses(), 0);
+  }
+}
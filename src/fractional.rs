@@ -0,0 +1,400 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{
+  cellpack::Cellpack, id::AlkaneId, parcel::{AlkaneTransfer, AlkaneTransferParcel},
+  response::CallResponse,
+};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::{
+  alkane_id_from_bytes, alkane_id_to_bytes, forward_except, PaymentToken, RoyaltyNFTCollection,
+  FRACTION_SHARE_TEMPLATE_ID,
+};
+
+/// Lifecycle of a fractional vault
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VaultStatus {
+  /// Orbital locked, shares circulating
+  Active,
+  /// A holder of every share took the orbital back
+  Redeemed,
+  /// Bought at the reserve price; share holders claim the payout
+  BoughtOut,
+}
+
+impl VaultStatus {
+  fn as_u128(&self) -> u128 {
+    match self {
+      VaultStatus::Active => 0,
+      VaultStatus::Redeemed => 1,
+      VaultStatus::BoughtOut => 2,
+    }
+  }
+
+  fn from_u128(value: u128) -> Result<VaultStatus> {
+    match value {
+      0 => Ok(VaultStatus::Active),
+      1 => Ok(VaultStatus::Redeemed),
+      2 => Ok(VaultStatus::BoughtOut),
+      _ => Err(anyhow!("Invalid vault status")),
+    }
+  }
+}
+
+/// An orbital locked in the collection against a fungible share alkane
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vault {
+  pub id: u128,
+  pub index: u128,
+  pub share_id: AlkaneId,
+  pub total_shares: u128,
+  pub payment_token: PaymentToken,
+  /// Buyout price, fixed when the shares are minted: no call changes it
+  /// while shares are outstanding
+  pub reserve_price: u128,
+  /// Buyout payout not yet claimed by share holders
+  pub payout_pool: u128,
+  /// Shares not yet exchanged for the buyout payout
+  pub shares_outstanding: u128,
+  pub status: VaultStatus,
+}
+
+impl Vault {
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(176);
+    bytes.extend_from_slice(&self.id.to_le_bytes());
+    bytes.extend_from_slice(&self.index.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.share_id));
+    bytes.extend_from_slice(&self.total_shares.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.payment_token.get_id()));
+    bytes.extend_from_slice(&self.reserve_price.to_le_bytes());
+    bytes.extend_from_slice(&self.payout_pool.to_le_bytes());
+    bytes.extend_from_slice(&self.shares_outstanding.to_le_bytes());
+    bytes.extend_from_slice(&self.status.as_u128().to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Vault> {
+    if bytes.len() != 176 {
+      return Err(anyhow!("Invalid vault data length"));
+    }
+
+    let token_id = alkane_id_from_bytes(&bytes[80..112])?;
+
+    Ok(Vault {
+      id: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+      index: u128::from_le_bytes(bytes[16..32].try_into().unwrap()),
+      share_id: alkane_id_from_bytes(&bytes[32..64])?,
+      total_shares: u128::from_le_bytes(bytes[64..80].try_into().unwrap()),
+      payment_token: PaymentToken::from_alkane_id(&token_id)
+        .ok_or_else(|| anyhow!("vault uses an unsupported payment token"))?,
+      reserve_price: u128::from_le_bytes(bytes[112..128].try_into().unwrap()),
+      payout_pool: u128::from_le_bytes(bytes[128..144].try_into().unwrap()),
+      shares_outstanding: u128::from_le_bytes(bytes[144..160].try_into().unwrap()),
+      status: VaultStatus::from_u128(u128::from_le_bytes(bytes[160..176].try_into().unwrap()))?,
+    })
+  }
+
+  /// Retire `supplied` shares of a bought-out vault and return their payout.
+  /// Pays against what is left so the last claimant receives any rounding remainder.
+  fn claim_payout(&mut self, supplied: u128) -> Result<u128> {
+    if supplied == 0 {
+      return Err(anyhow!("No shares of vault {} supplied", self.id));
+    }
+    if supplied > self.shares_outstanding {
+      return Err(anyhow!(
+        "{} shares of vault {} supplied, only {} are outstanding",
+        supplied, self.id, self.shares_outstanding
+      ));
+    }
+
+    let payout = self.payout_pool.checked_mul(supplied)
+      .ok_or_else(|| anyhow!("buyout payout too large to settle"))?
+      / self.shares_outstanding;
+
+    self.payout_pool -= payout;
+    self.shares_outstanding -= supplied;
+
+    Ok(payout)
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn vault_pointer(&self, vault_id: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/fractions/vaults/").select(&vault_id.to_le_bytes().to_vec())
+  }
+
+  fn vault_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/fractions/vault-count")
+  }
+
+  pub(crate) fn vault_count(&self) -> u128 {
+    self.vault_count_pointer().get_value::<u128>()
+  }
+
+  /// Active vault holding the orbital at `index`, if any
+  fn active_vault_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/fractions/active/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn vault(&self, vault_id: u128) -> Result<Vault> {
+    let bytes = self.vault_pointer(vault_id).get();
+    if bytes.is_empty() {
+      return Err(anyhow!("Vault {} does not exist", vault_id));
+    }
+
+    Vault::from_bytes(&bytes)
+  }
+
+  fn save_vault(&self, vault: &Vault) {
+    self.vault_pointer(vault.id).set(Arc::new(vault.to_bytes()));
+  }
+
+  /// Close an active vault, releasing its orbital index for a future vault
+  fn close_vault(&self, vault: &mut Vault, status: VaultStatus) {
+    vault.status = status;
+    self.save_vault(vault);
+    self.active_vault_pointer(vault.index).set_value::<u128>(0);
+  }
+
+  /// Full share supply of the active vault holding the orbital at `index`, if any
+  pub(crate) fn vault_shares_for(&self, index: u128) -> Result<Option<AlkaneTransfer>> {
+    let vault_id = self.active_vault_pointer(index).get_value::<u128>();
+    if vault_id == 0 {
+      return Ok(None);
+    }
+
+    let vault = self.vault(vault_id)?;
+    Ok(Some(AlkaneTransfer { id: vault.share_id, value: vault.total_shares }))
+  }
+
+  fn active_vault(&self, vault_id: u128) -> Result<Vault> {
+    let vault = self.vault(vault_id)?;
+    if vault.status != VaultStatus::Active {
+      return Err(anyhow!("Vault {} is no longer active", vault_id));
+    }
+
+    Ok(vault)
+  }
+
+  /// Deploy the share alkane for a vault and return the minted supply
+  fn deploy_shares(&self, index: u128, total_shares: u128) -> Result<AlkaneTransfer> {
+    let cellpack = Cellpack {
+      target: AlkaneId {
+        block: 6,
+        tx: FRACTION_SHARE_TEMPLATE_ID,
+      },
+      inputs: vec![0x0, index, total_shares],
+    };
+
+    let sequence = self.sequence();
+    let response = self.call(&cellpack, &AlkaneTransferParcel::default(), self.fuel())?;
+
+    let share_id = AlkaneId {
+      block: 2,
+      tx: sequence,
+    };
+
+    let minted = response.alkanes.0.iter()
+      .filter(|transfer| transfer.id == share_id)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    if minted != total_shares {
+      return Err(anyhow!("share token returned {} of {} shares", minted, total_shares));
+    }
+
+    Ok(AlkaneTransfer { id: share_id, value: total_shares })
+  }
+
+  /// Lock the supplied orbital and mint `total_shares` of a new share alkane to the caller
+  /// Parameters: [index, total_shares, token_type (0=frBTC, 1=BUSD), reserve_price]
+  /// Returns the vault id
+  pub(crate) fn fractionalize(
+    &self,
+    index: u128,
+    total_shares: u128,
+    token_type: u128,
+    reserve_price: u128,
+  ) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let payment_token = PaymentToken::from_token_type(token_type)?;
+    if total_shares < 2 {
+      return Err(anyhow!("An orbital must be split into at least 2 shares"));
+    }
+    if reserve_price == 0 {
+      return Err(anyhow!("Reserve price must be greater than zero"));
+    }
+
+    let orbital_id = self.incoming_orbital(index)?;
+
+    // The orbital stays locked until redeemed or bought out
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: orbital_id, value: 1 }])?;
+
+    let shares = self.deploy_shares(index, total_shares)?;
+
+    // Vault ids start at 1 so 0 marks an orbital without an active vault
+    let vault_id = self.vault_count_pointer().get_value::<u128>() + 1;
+    self.vault_count_pointer().set_value::<u128>(vault_id);
+
+    self.save_vault(&Vault {
+      id: vault_id,
+      index,
+      share_id: shares.id,
+      total_shares,
+      payment_token,
+      reserve_price,
+      payout_pool: 0,
+      shares_outstanding: total_shares,
+      status: VaultStatus::Active,
+    });
+    self.active_vault_pointer(index).set_value::<u128>(vault_id);
+
+    response.alkanes.0.push(shares);
+    response.data = vault_id.to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  /// Return every share of a vault and receive its orbital
+  pub(crate) fn redeem_fractions(&self, vault_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let mut vault = self.active_vault(vault_id)?;
+
+    // The returned shares are retired with the vault
+    let shares = AlkaneTransfer { id: vault.share_id, value: vault.total_shares };
+    let mut response = forward_except(&context.incoming_alkanes, &[shares])?;
+
+    vault.shares_outstanding = 0;
+    self.close_vault(&mut vault, VaultStatus::Redeemed);
+
+    response.alkanes.0.push(AlkaneTransfer {
+      id: self.lookup_instance(vault.index)?,
+      value: 1,
+    });
+
+    Ok(response)
+  }
+
+  /// Pay the reserve price and receive the vaulted orbital. After royalties,
+  /// the payment is set aside for share holders.
+  pub(crate) fn buyout_vault(&self, vault_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let mut vault = self.active_vault(vault_id)?;
+
+    let payment = AlkaneTransfer { id: vault.payment_token.get_id(), value: vault.reserve_price };
+    let mut response = forward_except(&context.incoming_alkanes, &[payment])?;
+
+    vault.payout_pool = self.collect_sale_royalty(vault.index, vault.payment_token, vault.reserve_price)?;
+    self.close_vault(&mut vault, VaultStatus::BoughtOut);
+
+    response.alkanes.0.push(AlkaneTransfer {
+      id: self.lookup_instance(vault.index)?,
+      value: 1,
+    });
+
+    Ok(response)
+  }
+
+  /// Exchange the supplied shares of a bought-out vault for a pro-rata
+  /// share of the buyout payout
+  pub(crate) fn claim_buyout_proceeds(&self, vault_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let mut vault = self.vault(vault_id)?;
+    if vault.status != VaultStatus::BoughtOut {
+      return Err(anyhow!("Vault {} has not been bought out", vault_id));
+    }
+
+    let supplied = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.id == vault.share_id)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    let payout = vault.claim_payout(supplied)?;
+    self.save_vault(&vault);
+
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: vault.share_id, value: supplied }])?;
+
+    if payout > 0 {
+      response.alkanes.0.push(AlkaneTransfer { id: vault.payment_token.get_id(), value: payout });
+    }
+
+    Ok(response)
+  }
+
+  /// Returns: [id, index, share_block, share_tx, total_shares, token_block, token_tx,
+  ///  reserve_price, payout_pool, shares_outstanding, status (0=active, 1=redeemed, 2=bought out)]
+  pub(crate) fn get_vault(&self, vault_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.vault(vault_id)?.to_bytes();
+
+    Ok(response)
+  }
+
+  /// Returns the id of the active vault holding the orbital, or 0
+  pub(crate) fn get_orbital_vault(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.active_vault_pointer(index).get_value::<u128>().to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn bought_out(payout_pool: u128, total_shares: u128) -> Vault {
+    Vault {
+      id: 1,
+      index: 42,
+      share_id: AlkaneId { block: 2, tx: 300 },
+      total_shares,
+      payment_token: PaymentToken::FrBTC,
+      reserve_price: 1_000,
+      payout_pool,
+      shares_outstanding: total_shares,
+      status: VaultStatus::BoughtOut,
+    }
+  }
+
+  #[test]
+  fn share_holders_split_the_whole_payout() {
+    let mut vault = bought_out(1_000, 3);
+
+    let payouts: Vec<u128> = [1, 1, 1].iter().map(|shares| vault.claim_payout(*shares).unwrap()).collect();
+
+    assert_eq!(payouts, vec![333, 333, 334]);
+    assert_eq!(vault.payout_pool, 0);
+    assert_eq!(vault.shares_outstanding, 0);
+  }
+
+  #[test]
+  fn payout_is_proportional_to_shares() {
+    let mut vault = bought_out(900, 100);
+
+    assert_eq!(vault.claim_payout(25).unwrap(), 225);
+    assert_eq!(vault.claim_payout(75).unwrap(), 675);
+  }
+
+  #[test]
+  fn claims_beyond_outstanding_shares_are_rejected() {
+    let mut vault = bought_out(900, 10);
+    vault.claim_payout(6).unwrap();
+
+    assert!(vault.claim_payout(5).is_err());
+    assert!(vault.claim_payout(0).is_err());
+    assert_eq!(vault.shares_outstanding, 4);
+  }
+}
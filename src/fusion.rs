@@ -0,0 +1,218 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::svg_generator::{OrbitalTraits, SvgGenerator};
+use crate::{forward_except, RoyaltyNFTCollection};
+
+/// Parents of a fused orbital
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lineage {
+  pub parent_a: u128,
+  pub parent_b: u128,
+  /// 1 for a fusion of two minted orbitals, otherwise one more than the
+  /// higher generation of the two parents
+  pub generation: u128,
+}
+
+impl Lineage {
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(48);
+    bytes.extend_from_slice(&self.parent_a.to_le_bytes());
+    bytes.extend_from_slice(&self.parent_b.to_le_bytes());
+    bytes.extend_from_slice(&self.generation.to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Lineage> {
+    if bytes.len() != 48 {
+      return Err(anyhow!("Invalid lineage data length"));
+    }
+
+    Ok(Lineage {
+      parent_a: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+      parent_b: u128::from_le_bytes(bytes[16..32].try_into().unwrap()),
+      generation: u128::from_le_bytes(bytes[32..48].try_into().unwrap()),
+    })
+  }
+
+  /// Add the parents and generation to an orbital's attributes
+  fn annotate(&self, attributes: &mut Value) {
+    attributes["parents"] = json!([self.parent_a, self.parent_b]);
+    attributes["generation"] = json!(self.generation);
+  }
+}
+
+/// Combine parent traits: art style and pattern from the first parent,
+/// palette and symmetry from the second, the stronger energy of the two,
+/// and one complexity tier above the more complex parent
+fn fuse_traits(a: &OrbitalTraits, b: &OrbitalTraits) -> OrbitalTraits {
+  OrbitalTraits {
+    art_style: a.art_style,
+    color_palette: b.color_palette,
+    pattern_type: a.pattern_type,
+    complexity: (a.complexity.max(b.complexity) + 1).min(4),
+    symmetry: b.symmetry,
+    energy_level: a.energy_level.max(b.energy_level),
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn traits_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/traits/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn lineage_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/lineage/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn fusion_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/fusion/count")
+  }
+
+  /// Number of fused orbitals minted above max_mints
+  pub(crate) fn fusion_count(&self) -> u128 {
+    self.fusion_count_pointer().get_value::<u128>()
+  }
+
+  /// Exclusive upper bound on orbital indices, including fused orbitals
+  pub(crate) fn index_limit(&self) -> u128 {
+    self.max_mints() + self.fusion_count()
+  }
+
+  /// Traits the orbital at `index` is rendered from
+  pub(crate) fn orbital_traits(&self, index: u128) -> Result<OrbitalTraits> {
+    let bytes = self.traits_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(OrbitalTraits::from_index(index, &self.style_weights()?));
+    }
+
+    OrbitalTraits::from_bytes(&bytes)
+  }
+
+  pub(crate) fn lineage(&self, index: u128) -> Result<Option<Lineage>> {
+    let bytes = self.lineage_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(Lineage::from_bytes(&bytes)?))
+  }
+
+  /// Attributes of the orbital at `index`, with its lineage if it was fused
+  pub(crate) fn orbital_attributes(&self, index: u128) -> Result<Value> {
+    let mut attributes = SvgGenerator::get_attributes(&self.orbital_traits(index)?);
+    if let Some(lineage) = self.lineage(index)? {
+      lineage.annotate(&mut attributes);
+    }
+
+    Ok(attributes)
+  }
+
+  fn generation(&self, index: u128) -> Result<u128> {
+    Ok(self.lineage(index)?.map(|lineage| lineage.generation).unwrap_or(0))
+  }
+
+  /// Burn two supplied orbitals and mint a fused orbital inheriting their traits.
+  /// Fused orbitals take indices above max_mints, so fusion works after sellout.
+  /// Returns the fused orbital's index.
+  pub(crate) fn fuse(&self, index_a: u128, index_b: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    if index_a == index_b {
+      return Err(anyhow!("Cannot fuse orbital #{} with itself", index_a));
+    }
+
+    let orbital_a = self.incoming_orbital(index_a)?;
+    let orbital_b = self.incoming_orbital(index_b)?;
+    let mut response = forward_except(&context.incoming_alkanes, &[
+      AlkaneTransfer { id: orbital_a, value: 1 },
+      AlkaneTransfer { id: orbital_b, value: 1 },
+    ])?;
+
+    self.retire_orbital(index_a)?;
+    self.retire_orbital(index_b)?;
+
+    let fusion_count = self.fusion_count();
+    let index = self.max_mints() + fusion_count;
+    self.fusion_count_pointer().set_value::<u128>(fusion_count + 1);
+
+    let traits = fuse_traits(&self.orbital_traits(index_a)?, &self.orbital_traits(index_b)?);
+    let lineage = Lineage {
+      parent_a: index_a,
+      parent_b: index_b,
+      generation: self.generation(index_a)?.max(self.generation(index_b)?) + 1,
+    };
+
+    // Record traits before deploying the child so its metadata is complete from the start
+    self.traits_pointer(index).set(Arc::new(traits.to_bytes()));
+    self.lineage_pointer(index).set(Arc::new(lineage.to_bytes()));
+
+    response.alkanes.0.push(self.mint_orbital_at(index)?);
+    response.data = index.to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  /// Returns: [parent_a, parent_b, generation], all zero for unfused orbitals
+  pub(crate) fn get_lineage(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = match self.lineage(index)? {
+      Some(lineage) => lineage.to_bytes(),
+      None => vec![0u8; 48],
+    };
+
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn traits(art_style: u128, complexity: u128, energy_level: u128) -> OrbitalTraits {
+    OrbitalTraits {
+      art_style,
+      color_palette: art_style + 1,
+      pattern_type: art_style + 2,
+      complexity,
+      symmetry: art_style % 4,
+      energy_level,
+    }
+  }
+
+  #[test]
+  fn fused_traits_mix_parents() {
+    let fused = fuse_traits(&traits(1, 2, 0), &traits(5, 1, 3));
+
+    assert_eq!(fused.art_style, 1);
+    assert_eq!(fused.color_palette, 6);
+    assert_eq!(fused.pattern_type, 3);
+    assert_eq!(fused.complexity, 3);
+    assert_eq!(fused.symmetry, 1);
+    assert_eq!(fused.energy_level, 3);
+  }
+
+  #[test]
+  fn fused_complexity_is_capped() {
+    assert_eq!(fuse_traits(&traits(0, 4, 0), &traits(0, 4, 0)).complexity, 4);
+  }
+
+  #[test]
+  fn lineage_annotates_stored_trait_attributes() {
+    let fused = fuse_traits(&traits(1, 2, 0), &traits(5, 1, 3));
+    let mut attributes = SvgGenerator::get_attributes(&fused);
+    Lineage { parent_a: 7, parent_b: 12, generation: 2 }.annotate(&mut attributes);
+
+    assert_eq!(attributes["parents"], json!([7, 12]));
+    assert_eq!(attributes["generation"], json!(2));
+    assert_eq!(attributes["rarity_score"], SvgGenerator::get_attributes(&fused)["rarity_score"]);
+  }
+}
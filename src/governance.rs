@@ -0,0 +1,446 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::airdrop::HolderWeighting;
+use crate::storage_set::StorageSet;
+use crate::{PaymentToken, RoyaltyNFTCollection, BPS_DENOMINATOR};
+
+/// Share of eligible voting weight, in basis points, that must take part
+/// for a proposal to pass
+const QUORUM_BPS: u128 = 1000;
+
+/// Collection configuration change a proposal applies once passed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProposalAction {
+  /// SetRoyaltySchedule (opcode 209)
+  SetRoyaltySchedule { mode: u128 },
+  /// AddRoyaltyStep (opcode 210)
+  AddRoyaltyStep { threshold: u128, royalty_bps: u128 },
+  /// SetStakingShare (opcode 503)
+  SetStakingShare { share_bps: u128 },
+  /// Move treasury funds into a recipient's claimable proceeds (ClaimSaleProceeds)
+  TreasurySpend { payment_token: PaymentToken, amount: u128, recipient: AlkaneId },
+}
+
+impl ProposalAction {
+  fn from_parts(action: u128, args: [u128; 4]) -> Result<ProposalAction> {
+    match action {
+      0 => Ok(ProposalAction::SetRoyaltySchedule { mode: args[0] }),
+      1 => Ok(ProposalAction::AddRoyaltyStep { threshold: args[0], royalty_bps: args[1] }),
+      2 => Ok(ProposalAction::SetStakingShare { share_bps: args[0] }),
+      3 => Ok(ProposalAction::TreasurySpend {
+        payment_token: PaymentToken::from_token_type(args[0])?,
+        amount: args[1],
+        recipient: AlkaneId { block: args[2], tx: args[3] },
+      }),
+      _ => Err(anyhow!(
+        "Invalid proposal action. Use 0 for royalty schedule, 1 for royalty step, 2 for staking share, 3 for treasury spend"
+      )),
+    }
+  }
+
+  fn to_parts(self) -> (u128, [u128; 4]) {
+    match self {
+      ProposalAction::SetRoyaltySchedule { mode } => (0, [mode, 0, 0, 0]),
+      ProposalAction::AddRoyaltyStep { threshold, royalty_bps } => (1, [threshold, royalty_bps, 0, 0]),
+      ProposalAction::SetStakingShare { share_bps } => (2, [share_bps, 0, 0, 0]),
+      ProposalAction::TreasurySpend { payment_token, amount, recipient } => {
+        let token_type = match payment_token {
+          PaymentToken::FrBTC => 0,
+          PaymentToken::BUSD => 1,
+        };
+        (3, [token_type, amount, recipient.block, recipient.tx])
+      }
+    }
+  }
+}
+
+/// A holder vote on a single configuration change
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Proposal {
+  pub id: u128,
+  pub action: ProposalAction,
+  pub weighting: HolderWeighting,
+  pub start_height: u128,
+  pub end_height: u128,
+  /// Weight of the orbitals able to vote: unburned and minted before voting
+  /// opened. Recorded when voting opens.
+  pub eligible_weight: u128,
+  pub votes_for: u128,
+  pub votes_against: u128,
+  pub executed: bool,
+  /// Whether `eligible_weight` has been recorded
+  pub opened: bool,
+}
+
+impl Proposal {
+  fn to_bytes(self) -> Vec<u8> {
+    let (action, args) = self.action.to_parts();
+
+    let mut bytes = Vec::with_capacity(224);
+    bytes.extend_from_slice(&self.id.to_le_bytes());
+    bytes.extend_from_slice(&action.to_le_bytes());
+    for arg in args {
+      bytes.extend_from_slice(&arg.to_le_bytes());
+    }
+    bytes.extend_from_slice(&self.weighting.as_u128().to_le_bytes());
+    bytes.extend_from_slice(&self.start_height.to_le_bytes());
+    bytes.extend_from_slice(&self.end_height.to_le_bytes());
+    bytes.extend_from_slice(&self.eligible_weight.to_le_bytes());
+    bytes.extend_from_slice(&self.votes_for.to_le_bytes());
+    bytes.extend_from_slice(&self.votes_against.to_le_bytes());
+    bytes.extend_from_slice(&(self.executed as u128).to_le_bytes());
+    bytes.extend_from_slice(&(self.opened as u128).to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Proposal> {
+    if bytes.len() != 224 {
+      return Err(anyhow!("Invalid proposal data length"));
+    }
+
+    let field = |i: usize| u128::from_le_bytes(bytes[i * 16..(i + 1) * 16].try_into().unwrap());
+
+    Ok(Proposal {
+      id: field(0),
+      action: ProposalAction::from_parts(field(1), [field(2), field(3), field(4), field(5)])?,
+      weighting: HolderWeighting::from_u128(field(6))?,
+      start_height: field(7),
+      end_height: field(8),
+      eligible_weight: field(9),
+      votes_for: field(10),
+      votes_against: field(11),
+      executed: field(12) != 0,
+      opened: field(13) != 0,
+    })
+  }
+
+  fn passed(&self) -> bool {
+    self.opened
+      && self.votes_for.saturating_add(self.votes_against) >= quorum_for(self.eligible_weight)
+      && self.votes_for > self.votes_against
+  }
+}
+
+/// Turnout required out of `total_weight`, rounded up
+fn quorum_for(total_weight: u128) -> u128 {
+  total_weight.saturating_mul(QUORUM_BPS).div_ceil(BPS_DENOMINATOR)
+}
+
+impl RoyaltyNFTCollection {
+  fn proposal_pointer(&self, proposal_id: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/governance/proposals/").select(&proposal_id.to_le_bytes().to_vec())
+  }
+
+  fn proposal_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/governance/proposal-count")
+  }
+
+  /// Proposals whose voting has not yet ended
+  fn open_proposals(&self) -> StorageSet {
+    StorageSet::new("/governance/open")
+  }
+
+  fn voted_pointer(&self, proposal_id: u128, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/governance/voted/")
+      .select(&proposal_id.to_le_bytes().to_vec())
+      .select(&index.to_le_bytes().to_vec())
+  }
+
+  fn proposal(&self, proposal_id: u128) -> Result<Proposal> {
+    let bytes = self.proposal_pointer(proposal_id).get();
+    if bytes.is_empty() {
+      return Err(anyhow!("Proposal {} does not exist", proposal_id));
+    }
+
+    Proposal::from_bytes(&bytes)
+  }
+
+  fn save_proposal(&self, proposal: &Proposal) {
+    self.proposal_pointer(proposal.id).set(Arc::new(proposal.to_bytes()));
+  }
+
+  /// Keep the eligible weight of open proposals current. Called before any
+  /// orbital is minted or retired, and before votes are counted, so a proposal
+  /// whose voting has started records the weight in circulation before any
+  /// mint at or after its start height. An eligible orbital retired before it
+  /// votes (`retired`) no longer counts towards that weight.
+  pub(crate) fn track_voting_weight(&self, retired: Option<u128>) -> Result<()> {
+    let height = self.height() as u128;
+
+    let open = self.open_proposals();
+    for key in open.page(0, open.len()) {
+      let proposal_id = u128::from_le_bytes(key[..16].try_into().unwrap());
+      let mut proposal = self.proposal(proposal_id)?;
+      if height < proposal.start_height {
+        continue;
+      }
+
+      if !proposal.opened {
+        proposal.eligible_weight = self.settled_weight(proposal.weighting);
+        proposal.opened = true;
+      }
+
+      if let Some(index) = retired.filter(|_| height < proposal.end_height) {
+        if self.mint_height(index) < proposal.start_height
+          && self.voted_pointer(proposal.id, index).get_value::<u128>() == 0
+        {
+          let weight = self.holder_weight(proposal.weighting, index)?;
+          proposal.eligible_weight = proposal.eligible_weight.saturating_sub(weight);
+        }
+      }
+
+      self.save_proposal(&proposal);
+      if height >= proposal.end_height {
+        open.remove(&key);
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Count the votes of `indices` on an open proposal, skipping orbitals
+  /// minted after voting opened or that already voted
+  fn cast_votes(&self, proposal_id: u128, support: u128, indices: &[u128]) -> Result<()> {
+    if support > 1 {
+      return Err(anyhow!("support must be 0 (against) or 1 (for)"));
+    }
+
+    self.track_voting_weight(None)?;
+    let mut proposal = self.proposal(proposal_id)?;
+
+    let height = self.height() as u128;
+    if height < proposal.start_height || height >= proposal.end_height {
+      return Err(anyhow!(
+        "Proposal {} is open for voting from height {} to {}",
+        proposal_id, proposal.start_height, proposal.end_height
+      ));
+    }
+
+    let mut power = 0u128;
+    for index in indices {
+      if self.voted_pointer(proposal_id, *index).get_value::<u128>() != 0
+        || self.mint_height(*index) >= proposal.start_height
+      {
+        continue;
+      }
+
+      self.voted_pointer(proposal_id, *index).set_value::<u128>(1);
+      power += self.holder_weight(proposal.weighting, *index)?;
+    }
+
+    if power == 0 {
+      return Err(anyhow!("No eligible orbitals supplied to vote on proposal {}", proposal_id));
+    }
+
+    if support == 1 {
+      proposal.votes_for += power;
+    } else {
+      proposal.votes_against += power;
+    }
+    self.save_proposal(&proposal);
+
+    Ok(())
+  }
+
+  fn apply_proposal_action(&self, action: &ProposalAction) -> Result<()> {
+    match action {
+      ProposalAction::SetRoyaltySchedule { mode } => self.apply_royalty_schedule(*mode),
+      ProposalAction::AddRoyaltyStep { threshold, royalty_bps } => {
+        self.apply_royalty_step(*threshold, *royalty_bps)
+      }
+      ProposalAction::SetStakingShare { share_bps } => self.apply_staking_share(*share_bps),
+      ProposalAction::TreasurySpend { payment_token, amount, recipient } => {
+        self.debit_treasury(&payment_token.get_id(), *amount)?;
+        self.credit_sale_proceeds(recipient, &payment_token.get_id(), *amount)
+      }
+    }
+  }
+
+  /// Open a proposal for holders to vote on. Its quorum is QUORUM_BPS of the
+  /// weight of orbitals able to vote once voting opens.
+  /// Parameters: [action, arg0..arg3, weighting (0=one vote per orbital, 1=rarity), start_height, end_height]
+  /// Returns the proposal id
+  #[allow(clippy::too_many_arguments)]
+  pub(crate) fn create_proposal(
+    &self,
+    action: u128,
+    arg0: u128,
+    arg1: u128,
+    arg2: u128,
+    arg3: u128,
+    weighting: u128,
+    start_height: u128,
+    end_height: u128,
+  ) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let action = ProposalAction::from_parts(action, [arg0, arg1, arg2, arg3])?;
+    let weighting = HolderWeighting::from_u128(weighting)?;
+
+    if start_height < self.height() as u128 {
+      return Err(anyhow!("Voting cannot start in the past"));
+    }
+    if end_height <= start_height {
+      return Err(anyhow!("Voting must end after it starts"));
+    }
+
+    // Proposal ids start at 1
+    let proposal_id = self.proposal_count_pointer().get_value::<u128>() + 1;
+    self.proposal_count_pointer().set_value::<u128>(proposal_id);
+
+    self.save_proposal(&Proposal {
+      id: proposal_id,
+      action,
+      weighting,
+      start_height,
+      end_height,
+      eligible_weight: 0,
+      votes_for: 0,
+      votes_against: 0,
+      executed: false,
+      opened: false,
+    });
+    self.open_proposals().insert(&proposal_id.to_le_bytes());
+
+    response.data = proposal_id.to_le_bytes().to_vec();
+    Ok(response)
+  }
+
+  /// Vote with every supplied orbital; the orbitals are returned.
+  /// Orbitals minted after voting opened, or that already voted, are not counted.
+  /// Parameters: [proposal_id, support (0=against, 1=for)]
+  pub(crate) fn vote(&self, proposal_id: u128, support: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.cast_votes(proposal_id, support, &self.incoming_orbital_indices()?)?;
+
+    Ok(response)
+  }
+
+  /// Vote for the orbital at `index` while the collection holds it in custody,
+  /// presenting whatever holds it (a listing, stake, winning bid or bundle
+  /// receipt, every share of its vault, or the auth token while it is
+  /// auctioned); what was presented is returned
+  /// Parameters: [proposal_id, support (0=against, 1=for), index]
+  pub(crate) fn vote_in_custody(&self, proposal_id: u128, support: u128, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if !self.controls_custody_of(index)? {
+      return Err(anyhow!("The receipt holding orbital #{} was not supplied", index));
+    }
+
+    self.cast_votes(proposal_id, support, &[index])?;
+
+    Ok(response)
+  }
+
+  /// Apply a passed proposal after voting closes. Callable by anyone.
+  pub(crate) fn execute_proposal(&self, proposal_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.track_voting_weight(None)?;
+    let mut proposal = self.proposal(proposal_id)?;
+
+    if (self.height() as u128) < proposal.end_height {
+      return Err(anyhow!("Voting on proposal {} ends at height {}", proposal_id, proposal.end_height));
+    }
+    if proposal.executed {
+      return Err(anyhow!("Proposal {} was already executed", proposal_id));
+    }
+    if !proposal.passed() {
+      return Err(anyhow!("Proposal {} did not pass", proposal_id));
+    }
+
+    self.apply_proposal_action(&proposal.action)?;
+
+    proposal.executed = true;
+    self.save_proposal(&proposal);
+
+    Ok(response)
+  }
+
+  /// Returns: [id, action, arg0..arg3, weighting, start_height, end_height,
+  ///  eligible_weight, votes_for, votes_against, executed, opened]
+  pub(crate) fn get_proposal(&self, proposal_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.proposal(proposal_id)?.to_bytes();
+
+    Ok(response)
+  }
+
+  pub(crate) fn get_proposal_count(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.proposal_count_pointer().get_value::<u128>().to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn proposal(eligible_weight: u128, votes_for: u128, votes_against: u128) -> Proposal {
+    Proposal {
+      id: 1,
+      action: ProposalAction::SetStakingShare { share_bps: 2500 },
+      weighting: HolderWeighting::Equal,
+      start_height: 100,
+      end_height: 200,
+      eligible_weight,
+      votes_for,
+      votes_against,
+      executed: false,
+      opened: true,
+    }
+  }
+
+  #[test]
+  fn quorum_is_rounded_up_share_of_weight() {
+    assert_eq!(quorum_for(3333), 334);
+    assert_eq!(quorum_for(10), 1);
+    assert_eq!(quorum_for(0), 0);
+  }
+
+  #[test]
+  fn passes_only_at_quorum_with_majority() {
+    assert!(!proposal(3333, 333, 0).passed());
+    assert!(proposal(3333, 334, 0).passed());
+    assert!(proposal(3333, 200, 134).passed());
+    assert!(!proposal(3333, 167, 167).passed());
+    assert!(!proposal(3333, 100, 300).passed());
+  }
+
+  #[test]
+  fn single_vote_does_not_pass_large_collection() {
+    assert!(!proposal(3333, 1, 0).passed());
+  }
+
+  #[test]
+  fn burned_eligible_weight_lowers_quorum() {
+    assert!(!proposal(3340, 334, 0).passed());
+    assert!(proposal(3330, 334, 0).passed());
+  }
+
+  #[test]
+  fn proposal_never_opened_cannot_pass() {
+    let unopened = Proposal { opened: false, ..proposal(0, 5, 0) };
+    assert!(!unopened.passed());
+  }
+}
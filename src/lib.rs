@@ -14,12 +14,48 @@ use alkanes_support::{
 use anyhow::{anyhow, Result};
 use std::sync::Arc;
 
+mod airdrop;
+mod art;
+mod auction;
+mod bundle;
+mod burn;
+mod discount;
+mod fractional;
+mod fusion;
+mod governance;
+mod marketplace;
+mod mint_pass;
+mod mint_phase;
+mod receipt;
+mod referral;
+mod refund;
+mod royalty;
+mod staking;
+mod storage_set;
 mod svg_generator;
-use svg_generator::SvgGenerator;
+mod treasury;
+use discount::Discount;
+use svg_generator::{RenderOptions, SvgGenerator};
 
 /// Orbital template ID / Child contract template  
 const ROYALTY_NFT_ORBITAL_TEMPLATE_ID: u128 = ((2u128 << 32) | 3u128); // ✅ UPDATED: [2, 3] from deployment
 
+/// Fractional share template - initialized with [0x0, index, total_shares],
+/// returns the full share supply to the collection
+const FRACTION_SHARE_TEMPLATE_ID: u128 = ((2u128 << 32) | 4u128); // UPDATE after deploying the share template
+
+/// Bundle receipt template - initialized with [0x0, bundle_id], returns one
+/// receipt to the collection and delegates metadata to opcodes 1003/1004
+const BUNDLE_RECEIPT_TEMPLATE_ID: u128 = ((2u128 << 32) | 5u128); // UPDATE after deploying the receipt template
+
+/// Mint pass template - initialized with [0x0, supply], returns the whole
+/// pass supply to the collection; opcode 88 destroys the passes sent with the call
+const MINT_PASS_TEMPLATE_ID: u128 = ((2u128 << 32) | 6u128); // UPDATE after deploying the mint pass template
+
+/// Custody receipt template - initialized with [0x0, kind, serial], returns
+/// a single receipt unit to the collection
+const RECEIPT_TEMPLATE_ID: u128 = ((2u128 << 32) | 7u128); // UPDATE after deploying the receipt template
+
 /// Payment configuration - Multi-token support
 /// UPDATE THESE IDs FOR YOUR TARGET NETWORK
 
@@ -37,7 +73,7 @@ const MAX_PURCHASE_PER_TX: u128 = 3; // Maximum NFTs per transaction
 
 /// Royalty configuration
 const ROYALTY_PERCENTAGE: u128 = 500; // 5% in basis points (500/10000)
-const ROYALTY_RECIPIENT: AlkaneId = AlkaneId { block: 2, tx: 0 }; // Collection contract receives royalties
+const BPS_DENOMINATOR: u128 = 10000;
 
 /// Primary sales configuration
 const PRIMARY_SALES_RECIPIENT: AlkaneId = AlkaneId { block: 2, tx: 0 }; // Where primary mint payments go
@@ -64,6 +100,14 @@ impl PaymentToken {
         }
     }
     
+    fn from_token_type(token_type: u128) -> Result<PaymentToken> {
+        match token_type {
+            0 => Ok(PaymentToken::FrBTC),
+            1 => Ok(PaymentToken::BUSD),
+            _ => Err(anyhow!("Invalid token type. Use 0 for frBTC, 1 for BUSD")),
+        }
+    }
+
     fn from_alkane_id(id: &AlkaneId) -> Option<PaymentToken> {
         if *id == FRBTC_TOKEN_ID {
             Some(PaymentToken::FrBTC)
@@ -123,6 +167,18 @@ enum RoyaltyNFTCollectionMessage {
   #[returns(String)]
   GetInstanceIdentifier { index: u128 },
 
+  #[opcode(1003)]
+  #[returns(String)]
+  GetBundleAttributes { bundle_id: u128 },
+
+  #[opcode(1004)]
+  #[returns(Vec<u8>)]
+  GetBundleData { bundle_id: u128 },
+
+  #[opcode(1005)]
+  #[returns(Vec<u8>)]
+  GetStaticData { index: u128 },
+
   #[opcode(200)]
   #[returns(Vec<u8>)]
   GetRoyaltyInfo,
@@ -137,6 +193,330 @@ enum RoyaltyNFTCollectionMessage {
   #[opcode(203)]
   #[returns(Vec<u8>)]
   GetTokenPrices,
+
+  #[opcode(204)]
+  AddRoyaltyRecipient { recipient_block: u128, recipient_tx: u128, share_bps: u128 },
+
+  #[opcode(205)]
+  ClearRoyaltyRecipients,
+
+  #[opcode(206)]
+  DepositRoyalty { index: u128 },
+
+  #[opcode(207)]
+  #[returns(u128)]
+  GetOrbitalRoyaltyIncome { index: u128, token_block: u128, token_tx: u128 },
+
+  #[opcode(208)]
+  #[returns(u128)]
+  GetTreasuryBalance { token_block: u128, token_tx: u128 },
+
+  #[opcode(209)]
+  SetRoyaltySchedule { mode: u128 },
+
+  #[opcode(210)]
+  AddRoyaltyStep { threshold: u128, royalty_bps: u128 },
+
+  #[opcode(211)]
+  #[returns(Vec<u8>)]
+  GetRoyaltySchedule,
+
+  #[opcode(212)]
+  #[returns(Vec<u8>)]
+  GetOrbitalRoyaltyInfo { index: u128 },
+
+  #[opcode(213)]
+  #[returns(Vec<u8>)]
+  QuoteRoyalty { index: u128, sale_price: u128 },
+
+  #[opcode(214)]
+  SetRoyaltyOverride { index: u128, royalty_bps: u128, recipient_block: u128, recipient_tx: u128 },
+
+  #[opcode(215)]
+  ClearRoyaltyOverride { index: u128 },
+
+  #[opcode(216)]
+  #[returns(Vec<u8>)]
+  GetRoyaltyOverrides,
+
+  #[opcode(217)]
+  RegisterMarketplace { marketplace_block: u128, marketplace_tx: u128, has_custom_rate: u128, royalty_bps: u128 },
+
+  #[opcode(218)]
+  RemoveMarketplace { marketplace_block: u128, marketplace_tx: u128 },
+
+  #[opcode(219)]
+  #[returns(Vec<u8>)]
+  GetMarketplaces,
+
+  #[opcode(220)]
+  SeedTreasury { token_type: u128 },
+
+  #[opcode(300)]
+  ListOrbital { index: u128, token_type: u128, price: u128 },
+
+  #[opcode(301)]
+  CancelListing { index: u128 },
+
+  #[opcode(302)]
+  BuyOrbital { index: u128 },
+
+  #[opcode(303)]
+  #[returns(Vec<u8>)]
+  GetListings { offset: u128, limit: u128 },
+
+  #[opcode(304)]
+  ClaimSaleProceeds { token_type: u128 },
+
+  #[opcode(305)]
+  #[returns(u128)]
+  GetSaleProceeds { account_block: u128, account_tx: u128, token_type: u128 },
+
+  #[opcode(306)]
+  #[returns(u128)]
+  PlaceOffer { index: u128, token_type: u128, amount: u128 },
+
+  #[opcode(307)]
+  #[returns(u128)]
+  PlaceFloorBid { filter_type: u128, filter_value: u128, token_type: u128, amount: u128 },
+
+  #[opcode(308)]
+  CancelOffer { offer_id: u128 },
+
+  #[opcode(309)]
+  AcceptOffer { offer_id: u128, index: u128 },
+
+  #[opcode(310)]
+  ClaimOrbital { index: u128 },
+
+  #[opcode(311)]
+  #[returns(Vec<u8>)]
+  GetOffers { offset: u128, limit: u128 },
+
+  #[opcode(320)]
+  StartAuction { index: u128, token_type: u128, reserve_price: u128, end_height: u128 },
+
+  #[opcode(321)]
+  PlaceBid { index: u128 },
+
+  #[opcode(322)]
+  SettleAuction { index: u128 },
+
+  #[opcode(323)]
+  #[returns(Vec<u8>)]
+  GetAuction { index: u128 },
+
+  #[opcode(324)]
+  #[returns(Vec<u8>)]
+  GetAuctions { offset: u128, limit: u128 },
+
+  #[opcode(400)]
+  Burn { index: u128 },
+
+  #[opcode(401)]
+  SetBurnRedemption { token_block: u128, token_tx: u128, amount: u128 },
+
+  #[opcode(402)]
+  FundBurnRedemption,
+
+  #[opcode(403)]
+  #[returns(u128)]
+  GetBurnStatus { index: u128 },
+
+  #[opcode(404)]
+  #[returns(u128)]
+  GetCirculatingSupply,
+
+  #[opcode(405)]
+  #[returns(Vec<u8>)]
+  GetBurnRedemption,
+
+  #[opcode(410)]
+  #[returns(u128)]
+  Fuse { index_a: u128, index_b: u128 },
+
+  #[opcode(411)]
+  #[returns(Vec<u8>)]
+  GetLineage { index: u128 },
+
+  #[opcode(500)]
+  Stake { index: u128 },
+
+  #[opcode(501)]
+  Unstake { index: u128 },
+
+  #[opcode(502)]
+  ClaimStakingRewards { index: u128 },
+
+  #[opcode(503)]
+  SetStakingShare { share_bps: u128 },
+
+  #[opcode(504)]
+  #[returns(u128)]
+  GetPendingRewards { index: u128, token_block: u128, token_tx: u128 },
+
+  #[opcode(505)]
+  #[returns(Vec<u8>)]
+  GetStakingInfo,
+
+  #[opcode(520)]
+  #[returns(u128)]
+  CreateAirdrop { token_block: u128, token_tx: u128, weighting: u128, claim_deadline: u128 },
+
+  #[opcode(521)]
+  ClaimAirdrop { campaign_id: u128, index: u128 },
+
+  #[opcode(522)]
+  #[returns(Vec<u8>)]
+  GetAirdrop { campaign_id: u128 },
+
+  #[opcode(523)]
+  #[returns(u128)]
+  GetAirdropAllocation { campaign_id: u128, index: u128 },
+
+  #[opcode(524)]
+  SweepAirdrop { campaign_id: u128 },
+
+  #[opcode(540)]
+  #[returns(u128)]
+  CreateProposal { action: u128, arg0: u128, arg1: u128, arg2: u128, arg3: u128, weighting: u128, start_height: u128, end_height: u128 },
+
+  #[opcode(541)]
+  Vote { proposal_id: u128, support: u128 },
+
+  #[opcode(545)]
+  VoteInCustody { proposal_id: u128, support: u128, index: u128 },
+
+  #[opcode(542)]
+  ExecuteProposal { proposal_id: u128 },
+
+  #[opcode(543)]
+  #[returns(Vec<u8>)]
+  GetProposal { proposal_id: u128 },
+
+  #[opcode(544)]
+  #[returns(u128)]
+  GetProposalCount,
+
+  #[opcode(600)]
+  #[returns(u128)]
+  Fractionalize { index: u128, total_shares: u128, token_type: u128, reserve_price: u128 },
+
+  #[opcode(601)]
+  RedeemFractions { vault_id: u128 },
+
+  #[opcode(602)]
+  BuyoutVault { vault_id: u128 },
+
+  #[opcode(603)]
+  ClaimBuyoutProceeds { vault_id: u128 },
+
+  #[opcode(604)]
+  #[returns(Vec<u8>)]
+  GetVault { vault_id: u128 },
+
+  #[opcode(605)]
+  #[returns(u128)]
+  GetOrbitalVault { index: u128 },
+
+  #[opcode(620)]
+  #[returns(u128)]
+  WrapBundle,
+
+  #[opcode(621)]
+  UnwrapBundle { bundle_id: u128 },
+
+  #[opcode(622)]
+  #[returns(Vec<u8>)]
+  GetBundle { bundle_id: u128 },
+
+  #[opcode(700)]
+  IssueMintPasses { count: u128 },
+
+  #[opcode(701)]
+  SetMintPassPrice { token_type: u128, price: u128 },
+
+  #[opcode(702)]
+  BuyMintPasses { token_type: u128 },
+
+  #[opcode(703)]
+  #[returns(Vec<u8>)]
+  GetMintPassInfo,
+
+  #[opcode(720)]
+  RegisterReferrer { referrer_block: u128, referrer_tx: u128 },
+
+  #[opcode(721)]
+  RemoveReferrer { referrer_block: u128, referrer_tx: u128 },
+
+  #[opcode(722)]
+  SetReferralBps { referral_bps: u128 },
+
+  #[opcode(723)]
+  ClaimReferralRewards { token_type: u128 },
+
+  #[opcode(724)]
+  #[returns(Vec<u8>)]
+  GetReferrerStats { referrer_block: u128, referrer_tx: u128 },
+
+  #[opcode(725)]
+  #[returns(u128)]
+  GetReferralBps,
+
+  #[opcode(726)]
+  #[returns(Vec<u8>)]
+  IssueReferrerKey,
+
+  #[opcode(740)]
+  SetDiscount { token_block: u128, token_tx: u128, discount_bps: u128, usage_cap: u128 },
+
+  #[opcode(741)]
+  RemoveDiscount { token_block: u128, token_tx: u128 },
+
+  #[opcode(742)]
+  #[returns(Vec<u8>)]
+  GetDiscounts,
+
+  #[opcode(760)]
+  SetRefundGuarantee { min_minted: u128, deadline_height: u128 },
+
+  #[opcode(761)]
+  ClaimMintRefund { index: u128 },
+
+  #[opcode(762)]
+  #[returns(Vec<u8>)]
+  GetRefundGuarantee,
+
+  #[opcode(763)]
+  #[returns(Vec<u8>)]
+  GetMintReceipt { index: u128 },
+
+  #[opcode(764)]
+  ClaimPassRefund { token_type: u128 },
+
+  #[opcode(765)]
+  ReclaimMintEscrow,
+
+  #[opcode(780)]
+  SetMintPhase { phase: u128 },
+
+  #[opcode(781)]
+  SetCallerCap { phase: u128, caller_cap: u128 },
+
+  #[opcode(782)]
+  #[returns(Vec<u8>)]
+  GetMintPhase,
+
+  #[opcode(783)]
+  #[returns(u128)]
+  GetMintedBy { minter_block: u128, minter_tx: u128, phase: u128 },
+
+  #[opcode(800)]
+  SetStyleWeight { style: u128, weight: u128 },
+
+  #[opcode(801)]
+  #[returns(Vec<u8>)]
+  GetStyleWeights,
 }
 
 impl Token for RoyaltyNFTCollection {
@@ -172,6 +552,10 @@ impl RoyaltyNFTCollection {
     // Authorized mints
     self.only_owner()?;
 
+    if count > self.open_supply() {
+      return Err(anyhow!("Only {} orbitals remain outside outstanding mint passes", self.open_supply()));
+    }
+
     let mut minted_orbitals = Vec::new();
 
     for _ in 0..count {
@@ -183,31 +567,124 @@ impl RoyaltyNFTCollection {
     Ok(response)
   }
 
+  /// Mint with frBTC, BUSD or mint passes. Optional inputs
+  /// [referrer_block, referrer_tx] credit a registered referrer.
+  /// Near sellout only the remaining supply is minted and the rest of the
+  /// payment is refunded. Returns the number of orbitals minted.
   fn mint_orbital(&self) -> Result<CallResponse> {
     let context = self.context()?;
-    let mut response = CallResponse::forward(&context.incoming_alkanes);
 
-    // Calculate how many NFTs can be purchased with the provided payment
-    let purchase_count = self.calculate_purchase_count()?;
+    self.ensure_minting_open()?;
+
+    // Calculate how many NFTs can be purchased with the provided payment,
+    // within the caller's remaining allowance for this phase
+    let purchase_count = self.calculate_purchase_count()?.min(self.caller_allowance(&context.caller)?);
+    let referrer = self.mint_referrer()?;
+
+    // Mint passes are redeemed first; kept passes are burned on redemption
+    let passes_used = self.incoming_mint_passes()?.min(purchase_count);
+
+    // Supply held for outstanding mint passes is not available to paid mints
+    let paid_count = (purchase_count - passes_used).min(self.open_supply());
+    let filled_count = passes_used + paid_count;
+
+    if filled_count == 0 {
+      return Err(anyhow!("Alkane RoyaltyNFT have fully minted out"));
+    }
+
+    let discount = self.presented_discount()?;
+    let (payments, discounted_count) = plan_mint_payments(&context.incoming_alkanes.0, discount.as_ref(), paid_count);
+    if (payments.len() as u128) < paid_count {
+      return Err(anyhow!("Insufficient payment for {} more orbitals", paid_count - payments.len() as u128));
+    }
+    let mut kept = payments.clone();
+    if let Some(pass_id) = self.mint_pass_id()? {
+      kept.push(AlkaneTransfer { id: pass_id, value: passes_used });
+    }
+    let mut response = forward_except(&context.incoming_alkanes, &kept)?;
+
+    self.redeem_mint_passes(passes_used)?;
+    if let Some(discount) = &discount {
+      self.use_discount(discount, discounted_count)?;
+    }
+    if let Some(referrer) = &referrer {
+      self.record_referred_mints(referrer, paid_count);
+    }
+    self.record_caller_mints(&context.caller, filled_count);
     
     // Mint multiple orbitals in one transaction
     let mut minted_orbitals = Vec::new();
-    for _ in 0..purchase_count {
-      minted_orbitals.push(self.create_mint_transfer()?);
+    for _ in 0..passes_used {
+      let index = self.next_mint_index()?;
+      self.settle_pass_redemption(index)?;
+      minted_orbitals.push(self.mint_orbital_at(index)?);
+    }
+    for payment in &payments {
+      let index = self.next_mint_index()?;
+      self.settle_mint_payment(index, payment, referrer.as_ref())?;
+      minted_orbitals.push(self.mint_orbital_at(index)?);
     }
 
     response.alkanes.0.extend(minted_orbitals);
+    response.data = filled_count.to_le_bytes().to_vec();
 
     Ok(response)
   }
 
   fn create_mint_transfer(&self) -> Result<AlkaneTransfer> {
-    let index = self.instances_count();
+    let index = self.next_mint_index()?;
+
+    self.mint_orbital_at(index)
+  }
+
+  /// Next index available to regular mints: indices released by unsold
+  /// auctions first, then the cursor, skipping indices reserved for auction
+  /// or already minted at settlement
+  fn next_mint_index(&self) -> Result<u128> {
+    let released = self.released_indices();
+    if let Some(key) = released.page(0, 1).first() {
+      released.remove(key);
+      return Ok(u128::from_le_bytes(key[..16].try_into().unwrap()));
+    }
+
+    let mut index = self.mint_cursor();
+
+    while index < self.max_mints() && (self.is_reserved(index) || self.is_minted(index)) {
+      index += 1;
+    }
 
     if index >= self.max_mints() {
       return Err(anyhow!("Alkane RoyaltyNFT have fully minted out"));
     }
 
+    self.mint_cursor_pointer().set_value::<u128>(index + 1);
+
+    Ok(index)
+  }
+
+  fn mint_cursor_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/mint-cursor")
+  }
+
+  /// Lowest index regular mints may use. Collections that have never
+  /// reserved an index mint sequentially, so this starts at the instance count.
+  fn mint_cursor(&self) -> u128 {
+    if self.mint_cursor_pointer().get().is_empty() {
+      self.instances_count()
+    } else {
+      self.mint_cursor_pointer().get_value::<u128>()
+    }
+  }
+
+  /// Deploy the orbital child for a specific index
+  fn mint_orbital_at(&self, index: u128) -> Result<AlkaneTransfer> {
+    self.track_voting_weight(None)?;
+
+    // Pin the regular mint cursor before out-of-order mints change the instance count
+    if self.mint_cursor_pointer().get().is_empty() {
+      self.mint_cursor_pointer().set_value::<u128>(self.instances_count());
+    }
+
     let cellpack = Cellpack {
       target: AlkaneId {
         block: 6,
@@ -224,7 +701,9 @@ impl RoyaltyNFTCollection {
       tx: sequence,
     };
 
-    self.add_instance(&orbital_id)?;
+    self.add_instance(index, &orbital_id)?;
+    self.mint_heights_pointer(index).set_value::<u128>(self.height() as u128);
+    self.adjust_circulating(index, true)?;
 
     if response.alkanes.0.len() < 1 {
       Err(anyhow!("orbital token not returned with factory"))
@@ -237,6 +716,16 @@ impl RoyaltyNFTCollection {
     3333
   }
 
+  /// Orbitals minted below max_mints, excluding fused orbitals
+  fn primary_minted_count(&self) -> u128 {
+    self.instances_count() - self.fusion_count()
+  }
+
+  /// Indices below max_mints that are neither minted nor reserved for auction
+  fn unminted_supply(&self) -> u128 {
+    self.max_mints().saturating_sub(self.primary_minted_count() + self.reserved_count())
+  }
+
 
 
   fn get_name(&self) -> Result<CallResponse> {
@@ -279,8 +768,7 @@ impl RoyaltyNFTCollection {
     let context = self.context()?;
     let mut response = CallResponse::forward(&context.incoming_alkanes);
 
-    let attributes = SvgGenerator::get_attributes(index)?;
-    response.data = attributes.into_bytes();
+    response.data = self.orbital_attributes(index)?.to_string().into_bytes();
     Ok(response)
   }
 
@@ -288,7 +776,18 @@ impl RoyaltyNFTCollection {
     let context = self.context()?;
     let mut response = CallResponse::forward(&context.incoming_alkanes);
 
-    let svg = SvgGenerator::generate_svg(index)?;
+    let svg = SvgGenerator::generate_svg(index, &self.orbital_traits(index)?)?;
+    response.data = svg.into_bytes();
+    Ok(response)
+  }
+
+  /// Like GetData, with animations frozen for rasterizers and thumbnailers
+  fn get_static_data(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let options = RenderOptions { static_mode: true };
+    let svg = SvgGenerator::generate_svg_with_options(index, &self.orbital_traits(index)?, options)?;
     response.data = svg.into_bytes();
     Ok(response)
   }
@@ -305,18 +804,114 @@ impl RoyaltyNFTCollection {
     self.instances_pointer().set_value::<u128>(count);
   }
 
-  fn add_instance(&self, instance_id: &AlkaneId) -> Result<u128> {
+  fn mint_heights_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/mint-heights/").select(&index.to_le_bytes().to_vec())
+  }
+
+  /// Block height at which the orbital at `index` was minted
+  fn mint_height(&self, index: u128) -> u128 {
+    self.mint_heights_pointer(index).get_value::<u128>()
+  }
+
+  /// 1-based orbital index of an instance AlkaneId, 0 if it is not an orbital
+  fn instance_index_pointer(&self, instance_id: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/instance-index/").select(&alkane_id_to_bytes(instance_id))
+  }
+
+  /// Orbital index of an AlkaneId issued by this collection
+  fn orbital_index(&self, instance_id: &AlkaneId) -> Option<u128> {
+    match self.instance_index_pointer(instance_id).get_value::<u128>() {
+      0 => None,
+      index => Some(index - 1),
+    }
+  }
+
+  /// Indices of every orbital from this collection supplied with the call
+  fn incoming_orbital_indices(&self) -> Result<Vec<u128>> {
+    let context = self.context()?;
+
+    let mut indices: Vec<u128> = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.value > 0)
+      .filter_map(|transfer| self.orbital_index(&transfer.id))
+      .collect();
+    indices.sort();
+    indices.dedup();
+
+    Ok(indices)
+  }
+
+  /// Rarity score of the orbital at `index`
+  fn rarity_score(&self, index: u128) -> Result<u128> {
+    Ok(SvgGenerator::calculate_rarity_score(&self.orbital_traits(index)?))
+  }
+
+  fn circulating_rarity_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/circulating-rarity")
+  }
+
+  /// Sum of rarity scores over all unburned orbitals
+  fn circulating_rarity(&self) -> u128 {
+    self.circulating_rarity_pointer().get_value::<u128>()
+  }
+
+  fn block_mints_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/block-mints")
+  }
+
+  /// Count and total rarity of unburned orbitals minted in the current block
+  fn current_block_mints(&self) -> (u128, u128) {
+    let bytes = self.block_mints_pointer().get();
+    if bytes.len() != 48 || u128::from_le_bytes(bytes[..16].try_into().unwrap()) != self.height() as u128 {
+      return (0, 0);
+    }
+
+    (
+      u128::from_le_bytes(bytes[16..32].try_into().unwrap()),
+      u128::from_le_bytes(bytes[32..48].try_into().unwrap()),
+    )
+  }
+
+  /// Track circulating rarity, and the current block's share of circulation,
+  /// as the orbital at `index` is minted or retired
+  fn adjust_circulating(&self, index: u128, minted: bool) -> Result<()> {
+    let score = self.rarity_score(index)?;
+    let total = if minted {
+      self.circulating_rarity().saturating_add(score)
+    } else {
+      self.circulating_rarity().saturating_sub(score)
+    };
+
+    self.circulating_rarity_pointer().set_value::<u128>(total);
+
+    let height = self.height() as u128;
+    if self.mint_height(index) == height {
+      let (count, rarity) = self.current_block_mints();
+      let (count, rarity) = if minted {
+        (count + 1, rarity.saturating_add(score))
+      } else {
+        (count.saturating_sub(1), rarity.saturating_sub(score))
+      };
+
+      let mut bytes = Vec::with_capacity(48);
+      bytes.extend_from_slice(&height.to_le_bytes());
+      bytes.extend_from_slice(&count.to_le_bytes());
+      bytes.extend_from_slice(&rarity.to_le_bytes());
+      self.block_mints_pointer().set(Arc::new(bytes));
+    }
+
+    Ok(())
+  }
+
+  fn add_instance(&self, index: u128, instance_id: &AlkaneId) -> Result<u128> {
     let count = self.instances_count();
     let new_count = count.checked_add(1)
       .ok_or_else(|| anyhow!("instances count overflow"))?;
 
-    let mut bytes = Vec::with_capacity(32);
-    bytes.extend_from_slice(&instance_id.block.to_le_bytes());
-    bytes.extend_from_slice(&instance_id.tx.to_le_bytes());
-
-    let bytes_vec = new_count.to_le_bytes().to_vec();
+    // Instances are stored at 1-based indices
+    let bytes_vec = (index + 1).to_le_bytes().to_vec();
     let mut instance_pointer = self.instances_pointer().select(&bytes_vec);
-    instance_pointer.set(Arc::new(bytes));
+    instance_pointer.set(Arc::new(alkane_id_to_bytes(instance_id)));
+    self.instance_index_pointer(instance_id).set_value::<u128>(index + 1);
     
     self.set_instances_count(new_count);
     
@@ -343,18 +938,23 @@ impl RoyaltyNFTCollection {
     let context = self.context()?;
     
     let mut total_purchase_count = 0u128;
+    let mint_pass_id = self.mint_pass_id()?;
+    let discount = self.presented_discount()?;
     
-    // Calculate purchase count for each supported token
+    // Each mint pass pays for one orbital
     for transfer in &context.incoming_alkanes.0 {
-      if let Some(payment_token) = PaymentToken::from_alkane_id(&transfer.id) {
-        let price_per_mint = payment_token.get_price_per_mint();
-        let purchase_count_for_token = transfer.value / price_per_mint;
-        total_purchase_count += purchase_count_for_token;
+      if Some(transfer.id) == mint_pass_id {
+        total_purchase_count += transfer.value;
       }
     }
+
+    // Supported tokens pay for as many orbitals as they cover, at the
+    // discounted price while the discount has uses left
+    let (payments, _) = plan_mint_payments(&context.incoming_alkanes.0, discount.as_ref(), MAX_PURCHASE_PER_TX);
+    total_purchase_count += payments.len() as u128;
     
     if total_purchase_count == 0 {
-      return Err(anyhow!("No valid payment provided. Accepted tokens: frBTC ({}), BUSD ({})", 
+      return Err(anyhow!("No valid payment provided. Accepted tokens: frBTC ({}), BUSD ({}), mint passes (1)", 
                          FRBTC_AMOUNT_PER_MINT, BUSD_AMOUNT_PER_MINT));
     }
     
@@ -387,6 +987,45 @@ impl RoyaltyNFTCollection {
     Ok(())
   }
 
+  /// Like only_owner, but allows other alkanes to accompany the collection token
+  fn owner_with_alkanes(&self) -> Result<()> {
+    let context = self.context()?;
+
+    let supplied = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.id == context.myself)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    if supplied < 1 {
+      return Err(anyhow!(
+        "less than 1 unit of collection token supplied to authenticate"
+      ));
+    }
+
+    Ok(())
+  }
+
+  fn is_minted(&self, index: u128) -> bool {
+    self.instances_pointer().select(&(index + 1).to_le_bytes().to_vec()).get().len() == 32
+  }
+
+  /// Verify the call carries the orbital at `index` and return its AlkaneId
+  fn incoming_orbital(&self, index: u128) -> Result<AlkaneId> {
+    let context = self.context()?;
+    let orbital_id = self.lookup_instance(index)?;
+
+    let supplied = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.id == orbital_id)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    if supplied < 1 {
+      return Err(anyhow!("Orbital #{} ({}:{}) was not supplied", index, orbital_id.block, orbital_id.tx));
+    }
+
+    Ok(orbital_id)
+  }
+
   fn lookup_instance(&self, index: u128) -> Result<AlkaneId> {
     // Add 1 to index since instances are stored at 1-based indices
     let storage_index = index + 1;
@@ -399,13 +1038,7 @@ impl RoyaltyNFTCollection {
       return Err(anyhow!("Invalid instance data length"));
     }
 
-    let block_bytes = &bytes[..16];
-    let tx_bytes = &bytes[16..];
-
-    let block = u128::from_le_bytes(block_bytes.try_into().unwrap());
-    let tx = u128::from_le_bytes(tx_bytes.try_into().unwrap());
-
-    Ok(AlkaneId { block, tx })
+    alkane_id_from_bytes(&bytes)
   }
 
   fn get_instance_alkane_id(&self, index: u128) -> Result<CallResponse> {
@@ -414,11 +1047,7 @@ impl RoyaltyNFTCollection {
 
     let instance_id = self.lookup_instance(index)?;
 
-    let mut bytes = Vec::with_capacity(32);
-    bytes.extend_from_slice(&instance_id.block.to_le_bytes());
-    bytes.extend_from_slice(&instance_id.tx.to_le_bytes());
-
-    response.data = bytes;
+    response.data = alkane_id_to_bytes(&instance_id);
     Ok(response)
   }
 
@@ -433,20 +1062,6 @@ impl RoyaltyNFTCollection {
     Ok(response)
   }
 
-  fn get_royalty_info(&self) -> Result<CallResponse> {
-    let context = self.context()?;
-    let mut response = CallResponse::forward(&context.incoming_alkanes);
-    
-    // Return royalty info: [percentage, recipient_block, recipient_tx]
-    let mut data = Vec::new();
-    data.extend_from_slice(&ROYALTY_PERCENTAGE.to_le_bytes());
-    data.extend_from_slice(&context.myself.block.to_le_bytes()); // Collection contract receives royalties
-    data.extend_from_slice(&context.myself.tx.to_le_bytes());
-    
-    response.data = data;
-    Ok(response)
-  }
-
   /// Withdraw funds for a specific token type
   /// Parameters: [token_type (0=frBTC, 1=BUSD), amount]
   fn withdraw_funds(&self, token_type: u128, amount: u128) -> Result<CallResponse> {
@@ -462,12 +1077,11 @@ impl RoyaltyNFTCollection {
     }
     
     // Determine which token to withdraw
-    let payment_token = match token_type {
-      0 => PaymentToken::FrBTC,
-      1 => PaymentToken::BUSD,
-      _ => return Err(anyhow!("Invalid token type. Use 0 for frBTC, 1 for BUSD")),
-    };
+    let payment_token = PaymentToken::from_token_type(token_type)?;
     
+    // Only funds credited to the treasury ledger can leave the contract
+    self.debit_treasury(&payment_token.get_id(), amount)?;
+
     // Transfer the requested amount of the specified token to the caller
     response.alkanes.0.push(AlkaneTransfer {
       id: payment_token.get_id(),
@@ -506,8 +1120,187 @@ impl RoyaltyNFTCollection {
   }
 }
 
+/// Forward incoming alkanes back to the caller, minus the amounts the
+/// contract keeps
+fn forward_except(incoming: &AlkaneTransferParcel, kept: &[AlkaneTransfer]) -> Result<CallResponse> {
+  let mut remaining = incoming.0.clone();
+
+  for keep in kept {
+    let mut outstanding = keep.value;
+    for transfer in remaining.iter_mut().filter(|transfer| transfer.id == keep.id) {
+      let taken = transfer.value.min(outstanding);
+      transfer.value -= taken;
+      outstanding -= taken;
+    }
+
+    if outstanding > 0 {
+      return Err(anyhow!(
+        "Insufficient {}:{} supplied: {} more units required",
+        keep.id.block, keep.id.tx, outstanding
+      ));
+    }
+  }
+
+  remaining.retain(|transfer| transfer.value > 0);
+  Ok(CallResponse::forward(&AlkaneTransferParcel(remaining)))
+}
+
+/// Payment taken for each of up to `max_count` paid mints, drawn from the
+/// supported payment transfers in order. The first `remaining_uses` mints
+/// are charged the discounted price and the rest the full price. Returns the
+/// payments and how many of them were discounted.
+fn plan_mint_payments(
+  transfers: &[AlkaneTransfer],
+  discount: Option<&Discount>,
+  max_count: u128,
+) -> (Vec<AlkaneTransfer>, u128) {
+  let mut payments = Vec::new();
+  let mut discounted = 0u128;
+
+  for transfer in transfers {
+    let payment_token = match PaymentToken::from_alkane_id(&transfer.id) {
+      Some(payment_token) => payment_token,
+      None => continue,
+    };
+
+    let mut available = transfer.value;
+    while (payments.len() as u128) < max_count {
+      let discounted_mint = discount.map_or(false, |discount| discounted < discount.remaining_uses());
+      let price = match discount {
+        Some(discount) if discounted_mint => discount.apply(payment_token.get_price_per_mint()),
+        _ => payment_token.get_price_per_mint(),
+      };
+      if available < price {
+        break;
+      }
+
+      if discounted_mint {
+        discounted += 1;
+      }
+      available -= price;
+      payments.push(AlkaneTransfer { id: transfer.id, value: price });
+    }
+  }
+
+  (payments, discounted)
+}
+
+/// Pack an AlkaneId as [block, tx] little-endian u128s
+fn alkane_id_to_bytes(id: &AlkaneId) -> Vec<u8> {
+  let mut bytes = Vec::with_capacity(32);
+  bytes.extend_from_slice(&id.block.to_le_bytes());
+  bytes.extend_from_slice(&id.tx.to_le_bytes());
+  bytes
+}
+
+/// Unpack an AlkaneId stored as [block, tx] little-endian u128s
+fn alkane_id_from_bytes(bytes: &[u8]) -> Result<AlkaneId> {
+  if bytes.len() < 32 {
+    return Err(anyhow!("Invalid AlkaneId data length"));
+  }
+
+  let block = u128::from_le_bytes(bytes[..16].try_into().unwrap());
+  let tx = u128::from_le_bytes(bytes[16..32].try_into().unwrap());
+
+  Ok(AlkaneId { block, tx })
+}
+
 declare_alkane! {
   impl AlkaneResponder for RoyaltyNFTCollection {
     type Message = RoyaltyNFTCollectionMessage;
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn transfer(id: AlkaneId, value: u128) -> AlkaneTransfer {
+    AlkaneTransfer { id, value }
+  }
+
+  fn amounts(transfers: &[AlkaneTransfer]) -> Vec<(AlkaneId, u128)> {
+    transfers.iter().map(|transfer| (transfer.id, transfer.value)).collect()
+  }
+
+  fn discount(discount_bps: u128, usage_cap: u128, used: u128) -> Discount {
+    Discount { id: AlkaneId { block: 2, tx: 50 }, discount_bps, usage_cap, used }
+  }
+
+  #[test]
+  fn forward_except_returns_everything_not_kept() {
+    let orbital = AlkaneId { block: 2, tx: 900 };
+    let incoming = AlkaneTransferParcel(vec![
+      transfer(FRBTC_TOKEN_ID, 15_000),
+      transfer(orbital, 1),
+      transfer(FRBTC_TOKEN_ID, 5_000),
+    ]);
+
+    let response = forward_except(&incoming, &[transfer(FRBTC_TOKEN_ID, 18_000), transfer(orbital, 1)]).unwrap();
+
+    assert_eq!(amounts(&response.alkanes.0), vec![(FRBTC_TOKEN_ID, 2_000)]);
+  }
+
+  #[test]
+  fn forward_except_rejects_missing_alkanes() {
+    let incoming = AlkaneTransferParcel(vec![transfer(BUSD_TOKEN_ID, 999_999)]);
+
+    assert!(forward_except(&incoming, &[transfer(BUSD_TOKEN_ID, 1_000_000)]).is_err());
+    assert!(forward_except(&incoming, &[transfer(FRBTC_TOKEN_ID, 1)]).is_err());
+  }
+
+  #[test]
+  fn mint_payments_stop_at_max_count_and_leave_change() {
+    let transfers = [transfer(FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT * 5 + 7)];
+
+    let (payments, discounted) = plan_mint_payments(&transfers, None, 3);
+
+    assert_eq!(amounts(&payments), vec![(FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT); 3]);
+    assert_eq!(discounted, 0);
+  }
+
+  #[test]
+  fn mint_payments_draw_on_each_token_in_order() {
+    let transfers = [
+      transfer(AlkaneId { block: 2, tx: 50 }, 1),
+      transfer(FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT + 1),
+      transfer(BUSD_TOKEN_ID, BUSD_AMOUNT_PER_MINT * 2),
+    ];
+
+    let (payments, _) = plan_mint_payments(&transfers, None, 10);
+
+    assert_eq!(amounts(&payments), vec![
+      (FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT),
+      (BUSD_TOKEN_ID, BUSD_AMOUNT_PER_MINT),
+      (BUSD_TOKEN_ID, BUSD_AMOUNT_PER_MINT),
+    ]);
+  }
+
+  #[test]
+  fn discount_covers_only_remaining_uses() {
+    let discount = discount(5_000, 3, 2);
+    let half = FRBTC_AMOUNT_PER_MINT / 2;
+    let transfers = [transfer(FRBTC_TOKEN_ID, half + FRBTC_AMOUNT_PER_MINT * 2)];
+
+    let (payments, discounted) = plan_mint_payments(&transfers, Some(&discount), 3);
+
+    assert_eq!(amounts(&payments), vec![
+      (FRBTC_TOKEN_ID, half),
+      (FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT),
+      (FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT),
+    ]);
+    assert_eq!(discounted, 1);
+  }
+
+  #[test]
+  fn full_price_mint_is_not_bought_with_discounted_budget() {
+    let discount = discount(5_000, 1, 0);
+    let transfers = [transfer(FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT)];
+
+    let (payments, discounted) = plan_mint_payments(&transfers, Some(&discount), 3);
+
+    // Half price for the discounted mint, the other half does not cover a full-price one
+    assert_eq!(amounts(&payments), vec![(FRBTC_TOKEN_ID, FRBTC_AMOUNT_PER_MINT / 2)]);
+    assert_eq!(discounted, 1);
+  }
+}
@@ -0,0 +1,589 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::receipt::ReceiptKind;
+use crate::storage_set::StorageSet;
+use crate::svg_generator::OrbitalTraits;
+use crate::{
+  alkane_id_from_bytes, alkane_id_to_bytes, forward_except, PaymentToken, RoyaltyNFTCollection,
+  BPS_DENOMINATOR,
+};
+
+/// Maximum number of listings returned by a single GetListings call
+const MAX_LISTINGS_PAGE: u128 = 50;
+
+/// Maximum number of offers returned by a single GetOffers call
+const MAX_OFFERS_PAGE: u128 = 50;
+
+/// What an offer can be accepted against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OfferTarget {
+  /// A single orbital index
+  Orbital(u128),
+  /// Any orbital
+  Floor,
+  /// Any orbital with the given art style trait
+  ArtStyle(u128),
+  /// Any orbital with the given color palette trait
+  ColorPalette(u128),
+}
+
+impl OfferTarget {
+  fn to_parts(self) -> (u128, u128) {
+    match self {
+      OfferTarget::Orbital(index) => (0, index),
+      OfferTarget::Floor => (1, 0),
+      OfferTarget::ArtStyle(style) => (2, style),
+      OfferTarget::ColorPalette(palette) => (3, palette),
+    }
+  }
+
+  fn from_parts(kind: u128, value: u128) -> Result<OfferTarget> {
+    match kind {
+      0 => Ok(OfferTarget::Orbital(value)),
+      1 => Ok(OfferTarget::Floor),
+      2 => Ok(OfferTarget::ArtStyle(value)),
+      3 => Ok(OfferTarget::ColorPalette(value)),
+      _ => Err(anyhow!("Invalid offer target kind")),
+    }
+  }
+
+  fn matches(&self, index: u128, traits: &OrbitalTraits) -> bool {
+    match self {
+      OfferTarget::Orbital(target) => *target == index,
+      OfferTarget::Floor => true,
+      OfferTarget::ArtStyle(style) => traits.art_style == *style,
+      OfferTarget::ColorPalette(palette) => traits.color_palette == *palette,
+    }
+  }
+}
+
+/// Escrowed payment offered for an orbital
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Offer {
+  pub id: u128,
+  /// Receipt issued to the buyer; cancels the offer or claims the orbital
+  pub receipt: AlkaneId,
+  pub target: OfferTarget,
+  pub payment_token: PaymentToken,
+  pub amount: u128,
+}
+
+impl Offer {
+  fn to_bytes(self) -> Vec<u8> {
+    let (kind, value) = self.target.to_parts();
+
+    let mut bytes = Vec::with_capacity(128);
+    bytes.extend_from_slice(&self.id.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.receipt));
+    bytes.extend_from_slice(&kind.to_le_bytes());
+    bytes.extend_from_slice(&value.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.payment_token.get_id()));
+    bytes.extend_from_slice(&self.amount.to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Offer> {
+    if bytes.len() != 128 {
+      return Err(anyhow!("Invalid offer data length"));
+    }
+
+    let kind = u128::from_le_bytes(bytes[48..64].try_into().unwrap());
+    let value = u128::from_le_bytes(bytes[64..80].try_into().unwrap());
+    let token_id = alkane_id_from_bytes(&bytes[80..112])?;
+
+    Ok(Offer {
+      id: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+      receipt: alkane_id_from_bytes(&bytes[16..48])?,
+      target: OfferTarget::from_parts(kind, value)?,
+      payment_token: PaymentToken::from_alkane_id(&token_id)
+        .ok_or_else(|| anyhow!("offer uses an unsupported payment token"))?,
+      amount: u128::from_le_bytes(bytes[112..128].try_into().unwrap()),
+    })
+  }
+}
+
+/// An orbital escrowed in the collection and offered at a fixed price
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Listing {
+  pub index: u128,
+  /// Receipt issued to the seller; cancels the listing or collects its proceeds
+  pub receipt: AlkaneId,
+  pub payment_token: PaymentToken,
+  pub price: u128,
+}
+
+impl Listing {
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(96);
+    bytes.extend_from_slice(&self.index.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.receipt));
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.payment_token.get_id()));
+    bytes.extend_from_slice(&self.price.to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<Listing> {
+    if bytes.len() != 96 {
+      return Err(anyhow!("Invalid listing data length"));
+    }
+
+    let token_id = alkane_id_from_bytes(&bytes[48..80])?;
+
+    Ok(Listing {
+      index: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+      receipt: alkane_id_from_bytes(&bytes[16..48])?,
+      payment_token: PaymentToken::from_alkane_id(&token_id)
+        .ok_or_else(|| anyhow!("listing uses an unsupported payment token"))?,
+      price: u128::from_le_bytes(bytes[80..96].try_into().unwrap()),
+    })
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn listing_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/market/listings/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn listing_indices(&self) -> StorageSet {
+    StorageSet::new("/market/listing-indices")
+  }
+
+  pub(crate) fn listing(&self, index: u128) -> Result<Option<Listing>> {
+    let bytes = self.listing_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(Listing::from_bytes(&bytes)?))
+  }
+
+  fn remove_listing(&self, index: u128) {
+    self.listing_indices().remove(&index.to_le_bytes());
+    self.listing_pointer(index).set(Arc::new(Vec::new()));
+  }
+
+  /// Claimable balance of an account: a receipt alkane or a contract
+  fn sale_proceeds_pointer(&self, account: &AlkaneId, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/market/proceeds/")
+      .select(&alkane_id_to_bytes(account))
+      .select(&alkane_id_to_bytes(token))
+  }
+
+  /// Unclaimed proceeds of every account in `token`
+  fn outstanding_proceeds_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/market/proceeds-total/").select(&alkane_id_to_bytes(token))
+  }
+
+  pub(crate) fn outstanding_proceeds(&self, token: &AlkaneId) -> u128 {
+    self.outstanding_proceeds_pointer(token).get_value::<u128>()
+  }
+
+  pub(crate) fn sale_proceeds(&self, account: &AlkaneId, token: &AlkaneId) -> u128 {
+    self.sale_proceeds_pointer(account, token).get_value::<u128>()
+  }
+
+  pub(crate) fn credit_sale_proceeds(&self, account: &AlkaneId, token: &AlkaneId, amount: u128) -> Result<()> {
+    let proceeds = self.sale_proceeds(account, token)
+      .checked_add(amount)
+      .ok_or_else(|| anyhow!("sale proceeds overflow"))?;
+    self.sale_proceeds_pointer(account, token).set_value::<u128>(proceeds);
+    self.outstanding_proceeds_pointer(token).set_value::<u128>(self.outstanding_proceeds(token) + amount);
+
+    Ok(())
+  }
+
+  /// Deduct the royalty on a secondary sale of the orbital at `index`,
+  /// splitting it among the orbital's royalty recipients, and return the
+  /// seller's share
+  pub(crate) fn collect_sale_royalty(
+    &self,
+    index: u128,
+    payment_token: PaymentToken,
+    price: u128,
+  ) -> Result<u128> {
+    let royalty = price.checked_mul(self.royalty_bps_for(index)?)
+      .ok_or_else(|| anyhow!("sale price too large to settle"))?
+      / BPS_DENOMINATOR;
+
+    self.record_royalty_income(index, &payment_token.get_id(), royalty)?;
+    self.record_resale(index);
+
+    Ok(price - royalty)
+  }
+
+  /// Settle a sale of the orbital at `index`: collect the royalty and credit
+  /// the rest to `account` as claimable proceeds (ClaimSaleProceeds)
+  fn settle_sale(&self, index: u128, payment_token: PaymentToken, price: u128, account: &AlkaneId) -> Result<()> {
+    let seller_share = self.collect_sale_royalty(index, payment_token, price)?;
+    self.credit_sale_proceeds(account, &payment_token.get_id(), seller_share)
+  }
+
+  fn claimable_orbital_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/market/claimable-orbitals/").select(&index.to_le_bytes().to_vec())
+  }
+
+  /// Hold the orbital at `index` in custody until it is claimed with the
+  /// `owner` receipt, or by the `owner` contract
+  pub(crate) fn hold_orbital_for(&self, index: u128, owner: &AlkaneId) {
+    self.claimable_orbital_pointer(index).set(Arc::new(alkane_id_to_bytes(owner)));
+  }
+
+  /// Receipt or contract the orbital at `index` is held for, if awaiting a claim
+  pub(crate) fn orbital_claimant(&self, index: u128) -> Result<Option<AlkaneId>> {
+    let bytes = self.claimable_orbital_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(alkane_id_from_bytes(&bytes)?))
+  }
+
+  /// Escrow the supplied orbital and list it for sale, returning the listing
+  /// receipt that cancels it or collects its proceeds
+  /// Parameters: [index, token_type (0=frBTC, 1=BUSD), price]
+  pub(crate) fn list_orbital(&self, index: u128, token_type: u128, price: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let payment_token = PaymentToken::from_token_type(token_type)?;
+    if price == 0 {
+      return Err(anyhow!("Listing price must be greater than zero"));
+    }
+
+    let orbital_id = self.incoming_orbital(index)?;
+    if self.listing(index)?.is_some() {
+      return Err(anyhow!("Orbital #{} is already listed", index));
+    }
+
+    // The orbital stays in escrow until bought or cancelled
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: orbital_id, value: 1 }])?;
+
+    let receipt = self.issue_receipt(ReceiptKind::Listing)?;
+
+    let listing = Listing {
+      index,
+      receipt: receipt.id,
+      payment_token,
+      price,
+    };
+    self.listing_pointer(index).set(Arc::new(listing.to_bytes()));
+    self.listing_indices().insert(&index.to_le_bytes());
+
+    response.alkanes.0.push(receipt);
+
+    Ok(response)
+  }
+
+  /// Return the listing receipt and receive the escrowed orbital back
+  pub(crate) fn cancel_listing(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let listing = self.listing(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} is not listed", index))?;
+
+    let mut response = forward_except(&context.incoming_alkanes, &self.take_receipt(&listing.receipt)?)?;
+
+    self.remove_listing(index);
+
+    response.alkanes.0.push(AlkaneTransfer {
+      id: self.lookup_instance(index)?,
+      value: 1,
+    });
+
+    Ok(response)
+  }
+
+  /// Pay the listing price, deduct royalties and receive the orbital
+  pub(crate) fn buy_orbital(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let listing = self.listing(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} is not listed", index))?;
+
+    let payment = AlkaneTransfer { id: listing.payment_token.get_id(), value: listing.price };
+    let mut response = forward_except(&context.incoming_alkanes, &[payment])?;
+
+    self.remove_listing(index);
+
+    self.settle_sale(index, listing.payment_token, listing.price, &listing.receipt)?;
+
+    response.alkanes.0.push(AlkaneTransfer {
+      id: self.lookup_instance(index)?,
+      value: 1,
+    });
+
+    Ok(response)
+  }
+
+  /// Returns: [total, (index, receipt_block, receipt_tx, token_block, token_tx, price)...]
+  pub(crate) fn get_listings(&self, offset: u128, limit: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let indices = self.listing_indices();
+    let page = indices.page(offset, limit.min(MAX_LISTINGS_PAGE));
+
+    let mut data = Vec::with_capacity(16 + page.len() * 96);
+    data.extend_from_slice(&indices.len().to_le_bytes());
+    for key in page {
+      let index = u128::from_le_bytes(key[..16].try_into().unwrap());
+      if let Some(listing) = self.listing(index)? {
+        data.extend_from_slice(&listing.to_bytes());
+      }
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+
+  /// Withdraw the proceeds of every supplied receipt, plus those of the
+  /// calling contract. Receipts are returned with the payout.
+  /// Parameters: [token_type (0=frBTC, 1=BUSD)]
+  pub(crate) fn claim_sale_proceeds(&self, token_type: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let token = PaymentToken::from_token_type(token_type)?.get_id();
+
+    let mut amount = 0u128;
+    for account in self.controlled_accounts()? {
+      amount = amount.checked_add(self.sale_proceeds(&account, &token))
+        .ok_or_else(|| anyhow!("sale proceeds overflow"))?;
+      self.sale_proceeds_pointer(&account, &token).set_value::<u128>(0);
+    }
+
+    if amount == 0 {
+      return Err(anyhow!("No sale proceeds to claim"));
+    }
+    self.outstanding_proceeds_pointer(&token).set_value::<u128>(self.outstanding_proceeds(&token) - amount);
+
+    response.alkanes.0.push(AlkaneTransfer { id: token, value: amount });
+
+    Ok(response)
+  }
+
+  pub(crate) fn get_sale_proceeds(
+    &self,
+    account_block: u128,
+    account_tx: u128,
+    token_type: u128,
+  ) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let account = AlkaneId { block: account_block, tx: account_tx };
+    let token = PaymentToken::from_token_type(token_type)?.get_id();
+    response.data = self.sale_proceeds(&account, &token).to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  fn offer_pointer(&self, offer_id: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/market/offers/").select(&offer_id.to_le_bytes().to_vec())
+  }
+
+  fn offer_ids(&self) -> StorageSet {
+    StorageSet::new("/market/offer-ids")
+  }
+
+  fn next_offer_id_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/market/next-offer-id")
+  }
+
+  pub(crate) fn offer(&self, offer_id: u128) -> Result<Offer> {
+    let bytes = self.offer_pointer(offer_id).get();
+    if bytes.is_empty() {
+      return Err(anyhow!("Offer {} does not exist", offer_id));
+    }
+
+    Offer::from_bytes(&bytes)
+  }
+
+  fn remove_offer(&self, offer_id: u128) {
+    self.offer_ids().remove(&offer_id.to_le_bytes());
+    self.offer_pointer(offer_id).set(Arc::new(Vec::new()));
+  }
+
+  /// Escrow the payment and record an offer, returning the offer receipt and
+  /// the new offer id
+  fn place_offer_for(&self, target: OfferTarget, token_type: u128, amount: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let payment_token = PaymentToken::from_token_type(token_type)?;
+    if amount == 0 {
+      return Err(anyhow!("Offer amount must be greater than zero"));
+    }
+
+    let payment = AlkaneTransfer { id: payment_token.get_id(), value: amount };
+    let mut response = forward_except(&context.incoming_alkanes, &[payment])?;
+
+    // Offer ids start at 1
+    let offer_id = self.next_offer_id_pointer().get_value::<u128>() + 1;
+    self.next_offer_id_pointer().set_value::<u128>(offer_id);
+
+    let receipt = self.issue_receipt(ReceiptKind::Offer)?;
+
+    let offer = Offer {
+      id: offer_id,
+      receipt: receipt.id,
+      target,
+      payment_token,
+      amount,
+    };
+    self.offer_pointer(offer_id).set(Arc::new(offer.to_bytes()));
+    self.offer_ids().insert(&offer_id.to_le_bytes());
+
+    response.alkanes.0.push(receipt);
+    response.data = offer_id.to_le_bytes().to_vec();
+    Ok(response)
+  }
+
+  /// Offer on a specific orbital
+  /// Parameters: [index, token_type (0=frBTC, 1=BUSD), amount]
+  pub(crate) fn place_offer(&self, index: u128, token_type: u128, amount: u128) -> Result<CallResponse> {
+    if index >= self.index_limit() {
+      return Err(anyhow!("Orbital index {} is outside the collection", index));
+    }
+
+    self.place_offer_for(OfferTarget::Orbital(index), token_type, amount)
+  }
+
+  /// Bid on any orbital, optionally restricted to one art style or palette
+  /// Parameters: [filter_type (0=none, 1=art style, 2=color palette), filter_value, token_type, amount]
+  pub(crate) fn place_floor_bid(
+    &self,
+    filter_type: u128,
+    filter_value: u128,
+    token_type: u128,
+    amount: u128,
+  ) -> Result<CallResponse> {
+    let target = match filter_type {
+      0 => OfferTarget::Floor,
+      1 => OfferTarget::ArtStyle(filter_value),
+      2 => OfferTarget::ColorPalette(filter_value),
+      _ => return Err(anyhow!("Invalid filter type. Use 0 for none, 1 for art style, 2 for color palette")),
+    };
+
+    self.place_offer_for(target, token_type, amount)
+  }
+
+  /// Return the offer receipt to withdraw an open offer and refund its
+  /// escrowed payment
+  pub(crate) fn cancel_offer(&self, offer_id: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let offer = self.offer(offer_id)?;
+    let mut response = forward_except(&context.incoming_alkanes, &self.take_receipt(&offer.receipt)?)?;
+
+    self.remove_offer(offer_id);
+
+    response.alkanes.0.push(AlkaneTransfer {
+      id: offer.payment_token.get_id(),
+      value: offer.amount,
+    });
+
+    Ok(response)
+  }
+
+  /// Sell the supplied orbital into an open offer. The seller receives a sale
+  /// receipt whose proceeds are claimed like a listing's; the offer receipt
+  /// claims the orbital with ClaimOrbital.
+  pub(crate) fn accept_offer(&self, offer_id: u128, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let offer = self.offer(offer_id)?;
+    if !offer.target.matches(index, &self.orbital_traits(index)?) {
+      return Err(anyhow!("Orbital #{} does not satisfy offer {}", index, offer_id));
+    }
+
+    let orbital_id = self.incoming_orbital(index)?;
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: orbital_id, value: 1 }])?;
+
+    self.remove_offer(offer_id);
+    self.hold_orbital_for(index, &offer.receipt);
+
+    let receipt = self.issue_receipt(ReceiptKind::Sale)?;
+    self.settle_sale(index, offer.payment_token, offer.amount, &receipt.id)?;
+    response.alkanes.0.push(receipt);
+
+    Ok(response)
+  }
+
+  /// Collect an orbital held in custody, supplying the receipt it is held for
+  pub(crate) fn claim_orbital(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let owner = self.orbital_claimant(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} is not awaiting a claim", index))?;
+    let mut response = forward_except(&context.incoming_alkanes, &self.take_receipt(&owner)?)?;
+
+    self.claimable_orbital_pointer(index).set(Arc::new(Vec::new()));
+
+    response.alkanes.0.push(AlkaneTransfer {
+      id: self.lookup_instance(index)?,
+      value: 1,
+    });
+
+    Ok(response)
+  }
+
+  /// Returns: [total, (offer_id, receipt_block, receipt_tx, target_kind, target_value, token_block, token_tx, amount)...]
+  /// target_kind: 0=orbital index, 1=floor, 2=art style, 3=color palette
+  pub(crate) fn get_offers(&self, offset: u128, limit: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let offer_ids = self.offer_ids();
+    let page = offer_ids.page(offset, limit.min(MAX_OFFERS_PAGE));
+
+    let mut data = Vec::with_capacity(16 + page.len() * 128);
+    data.extend_from_slice(&offer_ids.len().to_le_bytes());
+    for key in page {
+      let offer_id = u128::from_le_bytes(key[..16].try_into().unwrap());
+      data.extend_from_slice(&self.offer(offer_id)?.to_bytes());
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn traits(art_style: u128, color_palette: u128) -> OrbitalTraits {
+    OrbitalTraits {
+      art_style,
+      color_palette,
+      pattern_type: 0,
+      complexity: 0,
+      symmetry: 0,
+      energy_level: 0,
+    }
+  }
+
+  #[test]
+  fn offer_targets_match_their_orbitals() {
+    let orbital = traits(3, 7);
+
+    assert!(OfferTarget::Orbital(12).matches(12, &orbital));
+    assert!(!OfferTarget::Orbital(12).matches(13, &orbital));
+    assert!(OfferTarget::Floor.matches(13, &orbital));
+    assert!(OfferTarget::ArtStyle(3).matches(13, &orbital));
+    assert!(!OfferTarget::ArtStyle(7).matches(13, &orbital));
+    assert!(OfferTarget::ColorPalette(7).matches(13, &orbital));
+    assert!(!OfferTarget::ColorPalette(3).matches(13, &orbital));
+  }
+
+  #[test]
+  fn unknown_offer_target_kind_is_rejected() {
+    assert!(OfferTarget::from_parts(4, 0).is_err());
+    assert_eq!(OfferTarget::from_parts(2, 5).unwrap(), OfferTarget::ArtStyle(5));
+  }
+}
@@ -0,0 +1,229 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{
+  cellpack::Cellpack, id::AlkaneId, parcel::{AlkaneTransfer, AlkaneTransferParcel},
+  response::CallResponse,
+};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::{
+  alkane_id_from_bytes, alkane_id_to_bytes, forward_except, PaymentToken, RoyaltyNFTCollection,
+  MINT_PASS_TEMPLATE_ID,
+};
+
+/// Mint pass template opcode that destroys the passes sent with the call
+const MINT_PASS_BURN_OPCODE: u128 = 88;
+
+impl RoyaltyNFTCollection {
+  fn mint_pass_id_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/mint-pass/id")
+  }
+
+  fn outstanding_mint_passes_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/mint-pass/outstanding")
+  }
+
+  fn mint_pass_price_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/mint-pass/price/").select(&alkane_id_to_bytes(token))
+  }
+
+  /// The mint pass alkane, once the first passes have been issued
+  pub(crate) fn mint_pass_id(&self) -> Result<Option<AlkaneId>> {
+    let bytes = self.mint_pass_id_pointer().get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(alkane_id_from_bytes(&bytes)?))
+  }
+
+  /// Passes issued and not yet redeemed
+  pub(crate) fn outstanding_mint_passes(&self) -> u128 {
+    self.outstanding_mint_passes_pointer().get_value::<u128>()
+  }
+
+  /// Unminted supply not already promised to outstanding mint passes
+  pub(crate) fn open_supply(&self) -> u128 {
+    self.unminted_supply().saturating_sub(self.outstanding_mint_passes())
+  }
+
+  /// Mint passes supplied with the current call
+  pub(crate) fn incoming_mint_passes(&self) -> Result<u128> {
+    let context = self.context()?;
+
+    Ok(match self.mint_pass_id()? {
+      Some(pass_id) => context.incoming_alkanes.0.iter()
+        .filter(|transfer| transfer.id == pass_id)
+        .map(|transfer| transfer.value)
+        .sum::<u128>(),
+      None => 0,
+    })
+  }
+
+  /// Retire `count` redeemed passes, releasing the supply they held. The
+  /// passes must already be kept from the incoming alkanes; they are burned
+  /// so they cannot be mistaken for unissued inventory.
+  pub(crate) fn redeem_mint_passes(&self, count: u128) -> Result<()> {
+    if count == 0 {
+      return Ok(());
+    }
+
+    let outstanding = self.outstanding_mint_passes();
+    if count > outstanding {
+      return Err(anyhow!("Only {} mint passes are outstanding", outstanding));
+    }
+
+    self.outstanding_mint_passes_pointer().set_value::<u128>(outstanding - count);
+
+    let pass_id = self.mint_pass_id()?.ok_or_else(|| anyhow!("No mint passes have been issued"))?;
+    let cellpack = Cellpack {
+      target: pass_id,
+      inputs: vec![MINT_PASS_BURN_OPCODE],
+    };
+    self.call(
+      &cellpack,
+      &AlkaneTransferParcel(vec![AlkaneTransfer { id: pass_id, value: count }]),
+      self.fuel(),
+    )?;
+
+    Ok(())
+  }
+
+  /// The mint pass alkane, deploying it on first use. The collection keeps the
+  /// whole pass supply as inventory and releases units as passes are issued.
+  fn ensure_mint_pass(&self) -> Result<AlkaneId> {
+    if let Some(pass_id) = self.mint_pass_id()? {
+      return Ok(pass_id);
+    }
+
+    let cellpack = Cellpack {
+      target: AlkaneId {
+        block: 6,
+        tx: MINT_PASS_TEMPLATE_ID,
+      },
+      inputs: vec![0x0, self.max_mints()],
+    };
+
+    let sequence = self.sequence();
+    let response = self.call(&cellpack, &AlkaneTransferParcel::default(), self.fuel())?;
+
+    let pass_id = AlkaneId {
+      block: 2,
+      tx: sequence,
+    };
+
+    let minted = response.alkanes.0.iter()
+      .filter(|transfer| transfer.id == pass_id)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    if minted != self.max_mints() {
+      return Err(anyhow!("mint pass returned {} of {} passes", minted, self.max_mints()));
+    }
+
+    self.mint_pass_id_pointer().set(Arc::new(alkane_id_to_bytes(&pass_id)));
+    Ok(pass_id)
+  }
+
+  /// Release `count` passes, each guaranteed one orbital from the remaining supply
+  fn issue_passes(&self, count: u128) -> Result<AlkaneTransfer> {
+    if count == 0 {
+      return Err(anyhow!("Must issue at least one mint pass"));
+    }
+
+    let open_supply = self.open_supply();
+    if count > open_supply {
+      return Err(anyhow!(
+        "Cannot issue {} mint passes: only {} orbitals remain unclaimed",
+        count, open_supply
+      ));
+    }
+
+    let pass_id = self.ensure_mint_pass()?;
+    self.outstanding_mint_passes_pointer().set_value::<u128>(self.outstanding_mint_passes() + count);
+
+    Ok(AlkaneTransfer { id: pass_id, value: count })
+  }
+
+  /// Issue mint passes to the owner for giveaways
+  /// Parameters: [count]
+  pub(crate) fn issue_mint_passes(&self, count: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.alkanes.0.push(self.issue_passes(count)?);
+
+    Ok(response)
+  }
+
+  /// Set the sale price of one mint pass, 0 stops sales in that token
+  /// Parameters: [token_type (0=frBTC, 1=BUSD), price]
+  pub(crate) fn set_mint_pass_price(&self, token_type: u128, price: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    let payment_token = PaymentToken::from_token_type(token_type)?;
+    self.mint_pass_price_pointer(&payment_token.get_id()).set_value::<u128>(price);
+
+    Ok(response)
+  }
+
+  /// Buy as many mint passes as the supplied payment covers; the remainder is returned.
+  /// Proceeds are escrowed like mint payments while a refund guarantee is active.
+  /// Parameters: [token_type (0=frBTC, 1=BUSD)]
+  pub(crate) fn buy_mint_passes(&self, token_type: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    self.ensure_minting_open()?;
+
+    let token = PaymentToken::from_token_type(token_type)?.get_id();
+    let price = self.mint_pass_price_pointer(&token).get_value::<u128>();
+    if price == 0 {
+      return Err(anyhow!("Mint passes are not for sale in this token"));
+    }
+
+    let paid = context.incoming_alkanes.0.iter()
+      .filter(|transfer| transfer.id == token)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    let count = (paid / price).min(self.open_supply());
+    if count == 0 {
+      return Err(anyhow!("Payment of {} does not cover one mint pass at {}", paid, price));
+    }
+
+    let cost = count * price;
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: token, value: cost }])?;
+
+    self.settle_pass_sale(&token, count, cost)?;
+    response.alkanes.0.push(self.issue_passes(count)?);
+
+    Ok(response)
+  }
+
+  /// Returns: [pass_block, pass_tx, outstanding, open_supply, frbtc_price, busd_price]
+  pub(crate) fn get_mint_pass_info(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let pass_id = self.mint_pass_id()?.unwrap_or(AlkaneId { block: 0, tx: 0 });
+
+    let mut data = Vec::with_capacity(96);
+    data.extend_from_slice(&alkane_id_to_bytes(&pass_id));
+    data.extend_from_slice(&self.outstanding_mint_passes().to_le_bytes());
+    data.extend_from_slice(&self.open_supply().to_le_bytes());
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      data.extend_from_slice(&self.mint_pass_price_pointer(&payment_token.get_id()).get_value::<u128>().to_le_bytes());
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+}
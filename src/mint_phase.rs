@@ -0,0 +1,136 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+
+use crate::{alkane_id_to_bytes, RoyaltyNFTCollection};
+
+impl RoyaltyNFTCollection {
+  fn mint_phase_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/mint-phase")
+  }
+
+  fn caller_cap_pointer(&self, phase: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/caller-caps/").select(&phase.to_le_bytes().to_vec())
+  }
+
+  /// Mints counted against `minter`, the calling contract id. Wallets calling
+  /// directly all share the 0:0 bucket.
+  fn minted_by_pointer(&self, phase: u128, minter: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/minted-by/")
+      .select(&phase.to_le_bytes().to_vec())
+      .select(&alkane_id_to_bytes(minter))
+  }
+
+  fn mint_phase(&self) -> u128 {
+    self.mint_phase_pointer().get_value::<u128>()
+  }
+
+  /// Lifetime MintOrbital limit per caller for `phase`, 0 for unlimited
+  fn caller_cap(&self, phase: u128) -> u128 {
+    self.caller_cap_pointer(phase).get_value::<u128>()
+  }
+
+  fn minted_by(&self, phase: u128, minter: &AlkaneId) -> u128 {
+    self.minted_by_pointer(phase, minter).get_value::<u128>()
+  }
+
+  /// Orbitals the calling contract `minter` may still mint in the current
+  /// phase. The cap is keyed by caller id, which a wallet cannot prove, so it
+  /// limits a minting contract (e.g. a launchpad or allowlist gate) rather than
+  /// an end user; direct wallet mints share one allowance.
+  pub(crate) fn caller_allowance(&self, minter: &AlkaneId) -> Result<u128> {
+    let phase = self.mint_phase();
+    let cap = self.caller_cap(phase);
+    if cap == 0 {
+      return Ok(u128::MAX);
+    }
+
+    let allowance = cap.saturating_sub(self.minted_by(phase, minter));
+    if allowance == 0 {
+      return Err(anyhow!("Mint limit of {} per caller reached for phase {}", cap, phase));
+    }
+
+    Ok(allowance)
+  }
+
+  pub(crate) fn record_caller_mints(&self, minter: &AlkaneId, count: u128) {
+    let phase = self.mint_phase();
+    self.minted_by_pointer(phase, minter).set_value::<u128>(self.minted_by(phase, minter) + count);
+  }
+
+  /// Switch the active mint phase; per-caller counts start fresh in each phase
+  /// Parameters: [phase]
+  pub(crate) fn set_mint_phase(&self, phase: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.mint_phase_pointer().set_value::<u128>(phase);
+
+    Ok(response)
+  }
+
+  /// Parameters: [phase, caller_cap (0=unlimited)]
+  pub(crate) fn set_caller_cap(&self, phase: u128, caller_cap: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.caller_cap_pointer(phase).set_value::<u128>(caller_cap);
+
+    Ok(response)
+  }
+
+  /// Returns: [phase, caller_cap]
+  pub(crate) fn get_mint_phase(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let phase = self.mint_phase();
+
+    let mut data = Vec::with_capacity(32);
+    data.extend_from_slice(&phase.to_le_bytes());
+    data.extend_from_slice(&self.caller_cap(phase).to_le_bytes());
+
+    response.data = data;
+    Ok(response)
+  }
+
+  /// Orbitals minted through MintOrbital by the calling contract `minter` during `phase`
+  /// Parameters: [minter_block, minter_tx, phase]
+  pub(crate) fn get_minted_by(&self, minter_block: u128, minter_tx: u128, phase: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let minter = AlkaneId { block: minter_block, tx: minter_tx };
+    response.data = self.minted_by(phase, &minter).to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn distinct_callers_have_separate_allowances() {
+    let collection = RoyaltyNFTCollection::default();
+    let launchpad = AlkaneId { block: 2, tx: 100 };
+    let gate = AlkaneId { block: 2, tx: 101 };
+
+    assert_ne!(
+      collection.minted_by_pointer(1, &launchpad).unwrap(),
+      collection.minted_by_pointer(1, &gate).unwrap()
+    );
+    assert_ne!(
+      collection.minted_by_pointer(1, &launchpad).unwrap(),
+      collection.minted_by_pointer(2, &launchpad).unwrap()
+    );
+  }
+}
@@ -0,0 +1,159 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{
+  cellpack::Cellpack, id::AlkaneId, parcel::{AlkaneTransfer, AlkaneTransferParcel},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::{RoyaltyNFTCollection, RECEIPT_TEMPLATE_ID};
+
+/// Caller id seen for calls made directly from a wallet rather than a contract
+const DIRECT_CALLER: AlkaneId = AlkaneId { block: 0, tx: 0 };
+
+/// What a custody receipt entitles its holder to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReceiptKind {
+  /// Cancel a listing, or collect its proceeds once it sells
+  Listing,
+  /// Cancel an offer, or claim the orbital once it is accepted
+  Offer,
+  /// Collect the proceeds of selling into an offer
+  Sale,
+  /// Claim an outbid refund, or the orbital once the auction is won
+  Bid,
+  /// Claim staking rewards and unstake the orbital
+  Stake,
+  /// Identify a referrer at mint time and claim its referral rewards
+  Referrer,
+}
+
+impl ReceiptKind {
+  fn as_u128(&self) -> u128 {
+    match self {
+      ReceiptKind::Listing => 0,
+      ReceiptKind::Offer => 1,
+      ReceiptKind::Sale => 2,
+      ReceiptKind::Bid => 3,
+      ReceiptKind::Stake => 4,
+      ReceiptKind::Referrer => 5,
+    }
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn receipt_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/receipt-count")
+  }
+
+  /// Receipts issued so far, of every kind
+  pub(crate) fn receipt_count(&self) -> u128 {
+    self.receipt_count_pointer().get_value::<u128>()
+  }
+
+  /// Deploy a single-unit receipt alkane. Every wallet calling the collection
+  /// directly shares the same caller id, so holding a receipt is what proves
+  /// a claim on escrowed orbitals and ledger balances.
+  pub(crate) fn issue_receipt(&self, kind: ReceiptKind) -> Result<AlkaneTransfer> {
+    // Receipt serials start at 1
+    let serial = self.receipt_count() + 1;
+    self.receipt_count_pointer().set_value::<u128>(serial);
+
+    let cellpack = Cellpack {
+      target: AlkaneId {
+        block: 6,
+        tx: RECEIPT_TEMPLATE_ID,
+      },
+      inputs: vec![0x0, kind.as_u128(), serial],
+    };
+
+    let sequence = self.sequence();
+    let response = self.call(&cellpack, &AlkaneTransferParcel::default(), self.fuel())?;
+
+    let receipt_id = AlkaneId {
+      block: 2,
+      tx: sequence,
+    };
+
+    let minted = response.alkanes.0.iter()
+      .filter(|transfer| transfer.id == receipt_id)
+      .map(|transfer| transfer.value)
+      .sum::<u128>();
+
+    if minted != 1 {
+      return Err(anyhow!("receipt template returned {} units instead of 1", minted));
+    }
+
+    Ok(AlkaneTransfer { id: receipt_id, value: 1 })
+  }
+
+  /// Accounts the current call can act for: the calling contract, if any,
+  /// and every alkane supplied with the call
+  pub(crate) fn controlled_accounts(&self) -> Result<Vec<AlkaneId>> {
+    let context = self.context()?;
+
+    let mut accounts: Vec<AlkaneId> = Vec::new();
+    for transfer in context.incoming_alkanes.0.iter().filter(|transfer| transfer.value > 0) {
+      if !accounts.contains(&transfer.id) {
+        accounts.push(transfer.id.clone());
+      }
+    }
+    if context.caller != DIRECT_CALLER && !accounts.contains(&context.caller) {
+      accounts.push(context.caller.clone());
+    }
+
+    Ok(accounts)
+  }
+
+  /// Whether the call acts for the orbital at `index` while the collection
+  /// holds it: through the listing, stake or winning bid receipt holding it,
+  /// the receipt of the bundle wrapping it, every share of its vault, or as
+  /// the seller of its auction
+  pub(crate) fn controls_custody_of(&self, index: u128) -> Result<bool> {
+    let accounts = self.controlled_accounts()?;
+
+    let holders = [
+      self.listing(index)?.map(|listing| listing.receipt),
+      self.staker(index)?,
+      self.orbital_claimant(index)?,
+      self.auction(index)?.filter(|auction| auction.held).map(|auction| auction.seller),
+      self.bundle_receipt_for(index)?,
+    ];
+    if holders.iter().flatten().any(|holder| accounts.contains(holder)) {
+      return Ok(true);
+    }
+
+    // Shares are fungible, so only the whole supply speaks for the orbital
+    match self.vault_shares_for(index)? {
+      Some(shares) => {
+        let context = self.context()?;
+        let supplied = context.incoming_alkanes.0.iter()
+          .filter(|transfer| transfer.id == shares.id)
+          .map(|transfer| transfer.value)
+          .sum::<u128>();
+        Ok(supplied >= shares.value)
+      }
+      None => Ok(false),
+    }
+  }
+
+  /// Check the call controls `account` and return what to keep from the
+  /// incoming alkanes so a presented receipt is retired with its record.
+  /// Contract accounts act as the caller and the collection's own token only
+  /// authenticates the owner, so neither is kept.
+  pub(crate) fn take_receipt(&self, account: &AlkaneId) -> Result<Vec<AlkaneTransfer>> {
+    let context = self.context()?;
+
+    let supplied = context.incoming_alkanes.0.iter()
+      .any(|transfer| transfer.id == *account && transfer.value > 0);
+
+    if supplied && *account != context.myself {
+      Ok(vec![AlkaneTransfer { id: account.clone(), value: 1 }])
+    } else if supplied || (context.caller == *account && *account != DIRECT_CALLER) {
+      Ok(Vec::new())
+    } else {
+      Err(anyhow!("Receipt {}:{} was not supplied", account.block, account.tx))
+    }
+  }
+}
@@ -0,0 +1,274 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+
+use crate::receipt::ReceiptKind;
+use crate::refund::GuaranteeState;
+use crate::{alkane_id_to_bytes, PaymentToken, RoyaltyNFTCollection, BPS_DENOMINATOR};
+
+/// Default share of a referred mint payment credited to the referrer (5%)
+const DEFAULT_REFERRAL_BPS: u128 = 500;
+
+impl RoyaltyNFTCollection {
+  fn referral_bps_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/referrals/bps")
+  }
+
+  fn referrer_pointer(&self, referrer: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/referrals/registered/").select(&alkane_id_to_bytes(referrer))
+  }
+
+  /// Referrers ever registered, including removed ones
+  fn referrer_count_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/referrals/count")
+  }
+
+  fn referred_mints_pointer(&self, referrer: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/referrals/mints/").select(&alkane_id_to_bytes(referrer))
+  }
+
+  fn referral_rewards_pointer(&self, referrer: &AlkaneId, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/referrals/rewards/")
+      .select(&alkane_id_to_bytes(referrer))
+      .select(&alkane_id_to_bytes(token))
+  }
+
+  /// Rewards on escrowed mint payments, claimable once the refund guarantee is met
+  fn pending_referral_pointer(&self, referrer: &AlkaneId, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/referrals/pending/")
+      .select(&alkane_id_to_bytes(referrer))
+      .select(&alkane_id_to_bytes(token))
+  }
+
+  fn referral_earnings_pointer(&self, referrer: &AlkaneId, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/referrals/earned/")
+      .select(&alkane_id_to_bytes(referrer))
+      .select(&alkane_id_to_bytes(token))
+  }
+
+  /// Share of referred mint payments credited to referrers, in basis points
+  fn referral_bps(&self) -> u128 {
+    if self.referral_bps_pointer().get().is_empty() {
+      DEFAULT_REFERRAL_BPS
+    } else {
+      self.referral_bps_pointer().get_value::<u128>()
+    }
+  }
+
+  fn is_referrer(&self, referrer: &AlkaneId) -> bool {
+    self.referrer_pointer(referrer).get_value::<u128>() != 0
+  }
+
+  pub(crate) fn referrer_count(&self) -> u128 {
+    self.referrer_count_pointer().get_value::<u128>()
+  }
+
+  fn add_referrer(&self, referrer: &AlkaneId) {
+    if !self.is_referrer(referrer) {
+      self.referrer_count_pointer().set_value::<u128>(self.referrer_count() + 1);
+    }
+    self.referrer_pointer(referrer).set_value::<u128>(1);
+  }
+
+  /// Referrer passed to MintOrbital as optional [referrer_block, referrer_tx] inputs
+  pub(crate) fn mint_referrer(&self) -> Result<Option<AlkaneId>> {
+    let context = self.context()?;
+
+    let referrer = match (context.inputs.get(1), context.inputs.get(2)) {
+      (Some(block), Some(tx)) => AlkaneId { block: *block, tx: *tx },
+      _ => return Ok(None),
+    };
+
+    if !self.is_referrer(&referrer) {
+      return Err(anyhow!("Referrer {}:{} is not registered", referrer.block, referrer.tx));
+    }
+    if self.controlled_accounts()?.contains(&referrer) {
+      return Err(anyhow!("Minters cannot refer themselves"));
+    }
+
+    Ok(Some(referrer))
+  }
+
+  /// Credit the referrer's cut of a mint payment and return the remainder
+  pub(crate) fn pay_referral(&self, referrer: &AlkaneId, payment: &AlkaneTransfer) -> Result<u128> {
+    let reward = payment.value.checked_mul(self.referral_bps())
+      .ok_or_else(|| anyhow!("mint payment too large to settle"))?
+      / BPS_DENOMINATOR;
+
+    // Rewards on escrowed payments wait on the refund guarantee like the rest of the payment
+    let rewards = if self.mint_proceeds_escrowed() {
+      self.pending_referral_pointer(referrer, &payment.id)
+    } else {
+      self.referral_rewards_pointer(referrer, &payment.id)
+    };
+    rewards.set_value::<u128>(rewards.get_value::<u128>() + reward);
+
+    let earnings = self.referral_earnings_pointer(referrer, &payment.id);
+    earnings.set_value::<u128>(earnings.get_value::<u128>() + reward);
+
+    Ok(payment.value - reward)
+  }
+
+  /// Take back a pending reward whose mint was refunded
+  pub(crate) fn reclaim_pending_referral(&self, referrer: &AlkaneId, token: &AlkaneId, amount: u128) -> Result<()> {
+    let pending = self.pending_referral_pointer(referrer, token);
+    let balance = pending.get_value::<u128>();
+    if balance < amount {
+      return Err(anyhow!("pending referral balance underflow"));
+    }
+    pending.set_value::<u128>(balance - amount);
+
+    let earnings = self.referral_earnings_pointer(referrer, token);
+    earnings.set_value::<u128>(earnings.get_value::<u128>().saturating_sub(amount));
+
+    Ok(())
+  }
+
+  /// Pending rewards are released when the guarantee is met, or when the
+  /// owner reclaims the escrow of a failed sale whose mints were not refunded
+  fn pending_referrals_released(&self) -> bool {
+    self.guarantee_state() == GuaranteeState::Met || self.refund_reclaimed()
+  }
+
+  fn claimable_referral_rewards(&self, referrer: &AlkaneId, token: &AlkaneId) -> u128 {
+    let mut claimable = self.referral_rewards_pointer(referrer, token).get_value::<u128>();
+    if self.pending_referrals_released() {
+      claimable += self.pending_referral_pointer(referrer, token).get_value::<u128>();
+    }
+    claimable
+  }
+
+  pub(crate) fn record_referred_mints(&self, referrer: &AlkaneId, count: u128) {
+    let mints = self.referred_mints_pointer(referrer);
+    mints.set_value::<u128>(mints.get_value::<u128>() + count);
+  }
+
+  /// Register a referrer by alkane id. Rewards are claimed by whoever can
+  /// supply that alkane, so it should be a single-unit key or a contract.
+  /// Parameters: [referrer_block, referrer_tx]
+  pub(crate) fn register_referrer(&self, referrer_block: u128, referrer_tx: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.add_referrer(&AlkaneId { block: referrer_block, tx: referrer_tx });
+
+    Ok(response)
+  }
+
+  /// Issue and register a referrer key for the owner to hand to a referrer.
+  /// Minters pass the key's id to MintOrbital and the holder supplies the key
+  /// to claim rewards. Returns the key id.
+  pub(crate) fn issue_referrer_key(&self) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let key = self.issue_receipt(ReceiptKind::Referrer)?;
+    self.add_referrer(&key.id);
+
+    response.data = alkane_id_to_bytes(&key.id);
+    response.alkanes.0.push(key);
+
+    Ok(response)
+  }
+
+  /// Stop crediting a referrer; rewards already earned remain claimable
+  /// Parameters: [referrer_block, referrer_tx]
+  pub(crate) fn remove_referrer(&self, referrer_block: u128, referrer_tx: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    let referrer = AlkaneId { block: referrer_block, tx: referrer_tx };
+    if !self.is_referrer(&referrer) {
+      return Err(anyhow!("Referrer {}:{} is not registered", referrer_block, referrer_tx));
+    }
+    self.referrer_pointer(&referrer).set_value::<u128>(0);
+
+    Ok(response)
+  }
+
+  /// Parameters: [referral_bps]
+  pub(crate) fn set_referral_bps(&self, referral_bps: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if referral_bps > BPS_DENOMINATOR {
+      return Err(anyhow!("Referral share cannot exceed {} bps", BPS_DENOMINATOR));
+    }
+    self.referral_bps_pointer().set_value::<u128>(referral_bps);
+
+    Ok(response)
+  }
+
+  /// Withdraw the accumulated referral rewards of every referrer the call
+  /// controls: supplied referrer keys, which are returned, and the calling
+  /// contract
+  /// Parameters: [token_type (0=frBTC, 1=BUSD)]
+  pub(crate) fn claim_referral_rewards(&self, token_type: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let token = PaymentToken::from_token_type(token_type)?.get_id();
+
+    let mut amount = 0u128;
+    for referrer in self.controlled_accounts()? {
+      let claimable = self.claimable_referral_rewards(&referrer, &token);
+      if claimable == 0 {
+        continue;
+      }
+
+      self.referral_rewards_pointer(&referrer, &token).set_value::<u128>(0);
+      if self.pending_referrals_released() {
+        self.pending_referral_pointer(&referrer, &token).set_value::<u128>(0);
+      }
+      amount += claimable;
+    }
+
+    if amount == 0 {
+      return Err(anyhow!("No referral rewards to claim"));
+    }
+
+    response.alkanes.0.push(AlkaneTransfer { id: token, value: amount });
+
+    Ok(response)
+  }
+
+  /// Returns: [registered, referred_mints, (earned, claimable) for frBTC then BUSD]
+  pub(crate) fn get_referrer_stats(&self, referrer_block: u128, referrer_tx: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let referrer = AlkaneId { block: referrer_block, tx: referrer_tx };
+
+    let mut data = Vec::with_capacity(96);
+    data.extend_from_slice(&(self.is_referrer(&referrer) as u128).to_le_bytes());
+    data.extend_from_slice(&self.referred_mints_pointer(&referrer).get_value::<u128>().to_le_bytes());
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      let token = payment_token.get_id();
+      data.extend_from_slice(&self.referral_earnings_pointer(&referrer, &token).get_value::<u128>().to_le_bytes());
+      data.extend_from_slice(&self.claimable_referral_rewards(&referrer, &token).to_le_bytes());
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+
+  pub(crate) fn get_referral_bps(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = self.referral_bps().to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+}
@@ -0,0 +1,494 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::{alkane_id_from_bytes, alkane_id_to_bytes, forward_except, PaymentToken, RoyaltyNFTCollection};
+
+/// Blocks after a failed sale's deadline during which minters can claim
+/// refunds before the owner may reclaim what is left (~30 days)
+const REFUND_WINDOW: u128 = 4320;
+
+/// Where a refund-guaranteed sale stands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GuaranteeState {
+  /// No guarantee configured; proceeds go straight to the treasury
+  None,
+  /// Proceeds held in escrow until the threshold is reached
+  Active,
+  /// Threshold reached before the deadline; escrow released to the treasury
+  Met,
+  /// Deadline passed below the threshold; minters may claim refunds
+  Failed,
+}
+
+impl GuaranteeState {
+  fn as_u128(&self) -> u128 {
+    match self {
+      GuaranteeState::None => 0,
+      GuaranteeState::Active => 1,
+      GuaranteeState::Met => 2,
+      GuaranteeState::Failed => 3,
+    }
+  }
+}
+
+/// Price paid for an orbital while its proceeds were escrowed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MintReceipt {
+  pub token: AlkaneId,
+  pub price: u128,
+  /// Referrer credited on the mint, 0:0 if none
+  pub referrer: AlkaneId,
+  /// Part of the price held for the referrer rather than in escrow
+  pub referral: u128,
+}
+
+impl MintReceipt {
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(96);
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.token));
+    bytes.extend_from_slice(&self.price.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&self.referrer));
+    bytes.extend_from_slice(&self.referral.to_le_bytes());
+    bytes
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Result<MintReceipt> {
+    if bytes.len() != 96 {
+      return Err(anyhow!("Invalid mint receipt data length"));
+    }
+
+    Ok(MintReceipt {
+      token: alkane_id_from_bytes(&bytes[..32])?,
+      price: u128::from_le_bytes(bytes[32..48].try_into().unwrap()),
+      referrer: alkane_id_from_bytes(&bytes[48..80])?,
+      referral: u128::from_le_bytes(bytes[80..96].try_into().unwrap()),
+    })
+  }
+}
+
+/// Part of `escrowed` paid for `count` of `passes` escrowed passes. Shares
+/// round down and the last passes take the remainder, so the escrow is spent
+/// exactly.
+fn pass_escrow_share(escrowed: u128, passes: u128, count: u128) -> u128 {
+  if count >= passes {
+    escrowed
+  } else {
+    escrowed * count / passes
+  }
+}
+
+impl RoyaltyNFTCollection {
+  fn refund_threshold_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/min-minted")
+  }
+
+  fn refund_deadline_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/deadline")
+  }
+
+  fn refund_met_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/met")
+  }
+
+  /// Set once the owner reclaims escrow left after the refund window
+  fn refund_reclaimed_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/reclaimed")
+  }
+
+  /// Paid mints whose proceeds went into escrow
+  fn escrowed_mints_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/paid-mints")
+  }
+
+  fn mint_escrow_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/escrow/").select(&alkane_id_to_bytes(token))
+  }
+
+  /// Mint pass sale proceeds held while the guarantee is active
+  fn pass_escrow_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/pass-escrow/").select(&alkane_id_to_bytes(token))
+  }
+
+  /// Passes sold for `token` whose proceeds are still in the pass escrow
+  fn escrowed_passes_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/pass-count/").select(&alkane_id_to_bytes(token))
+  }
+
+  fn mint_receipt_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/refund/receipts/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn mint_escrow(&self, token: &AlkaneId) -> u128 {
+    self.mint_escrow_pointer(token).get_value::<u128>()
+  }
+
+  fn pass_escrow(&self, token: &AlkaneId) -> u128 {
+    self.pass_escrow_pointer(token).get_value::<u128>()
+  }
+
+  fn escrowed_passes(&self, token: &AlkaneId) -> u128 {
+    self.escrowed_passes_pointer(token).get_value::<u128>()
+  }
+
+  /// Take `count` passes' share of the pass escrow for `token`
+  fn take_pass_escrow(&self, token: &AlkaneId, count: u128) -> u128 {
+    let escrowed = self.pass_escrow(token);
+    let passes = self.escrowed_passes(token);
+    let share = pass_escrow_share(escrowed, passes, count);
+
+    self.pass_escrow_pointer(token).set_value::<u128>(escrowed - share);
+    self.escrowed_passes_pointer(token).set_value::<u128>(passes.saturating_sub(count));
+    share
+  }
+
+  fn mint_receipt(&self, index: u128) -> Result<Option<MintReceipt>> {
+    let bytes = self.mint_receipt_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(MintReceipt::from_bytes(&bytes)?))
+  }
+
+  pub(crate) fn guarantee_state(&self) -> GuaranteeState {
+    let threshold = self.refund_threshold_pointer().get_value::<u128>();
+    if threshold == 0 {
+      GuaranteeState::None
+    } else if self.refund_met_pointer().get_value::<u128>() != 0 {
+      GuaranteeState::Met
+    } else if self.height() as u128 >= self.refund_deadline_pointer().get_value::<u128>() {
+      GuaranteeState::Failed
+    } else {
+      GuaranteeState::Active
+    }
+  }
+
+  /// Whether the owner has reclaimed the escrow after the refund window
+  pub(crate) fn refund_reclaimed(&self) -> bool {
+    self.refund_reclaimed_pointer().get_value::<u128>() != 0
+  }
+
+  /// Refunds are open from a failed deadline until the owner reclaims the escrow
+  fn ensure_refunds_open(&self) -> Result<()> {
+    if self.guarantee_state() != GuaranteeState::Failed {
+      return Err(anyhow!("Refunds are only available after a guaranteed sale misses its threshold"));
+    }
+    if self.refund_reclaimed() {
+      return Err(anyhow!("The refund window has closed"));
+    }
+
+    Ok(())
+  }
+
+  /// Whether primary sale proceeds are currently held back for refunds
+  pub(crate) fn mint_proceeds_escrowed(&self) -> bool {
+    self.guarantee_state() == GuaranteeState::Active
+  }
+
+  /// Refuse primary mints once a guaranteed sale has failed
+  pub(crate) fn ensure_minting_open(&self) -> Result<()> {
+    if self.guarantee_state() == GuaranteeState::Failed {
+      return Err(anyhow!("The sale missed its refund threshold; minting is closed and refunds are open"));
+    }
+
+    Ok(())
+  }
+
+  /// Account for one paid mint: escrow the proceeds while the guarantee is
+  /// active, otherwise credit the treasury. Any referral reward is taken first.
+  pub(crate) fn settle_mint_payment(
+    &self,
+    index: u128,
+    payment: &AlkaneTransfer,
+    referrer: Option<&AlkaneId>,
+  ) -> Result<()> {
+    let proceeds = match referrer {
+      Some(referrer) => self.pay_referral(referrer, payment)?,
+      None => payment.value,
+    };
+
+    if !self.mint_proceeds_escrowed() {
+      return self.credit_treasury(&payment.id, proceeds);
+    }
+
+    self.mint_escrow_pointer(&payment.id).set_value::<u128>(self.mint_escrow(&payment.id) + proceeds);
+
+    self.record_escrowed_mint(index, &MintReceipt {
+      token: payment.id,
+      price: payment.value,
+      referrer: referrer.copied().unwrap_or(AlkaneId { block: 0, tx: 0 }),
+      referral: payment.value - proceeds,
+    })
+  }
+
+  /// Account for a mint pass sale: escrow the proceeds while the guarantee
+  /// is active, otherwise credit the treasury
+  pub(crate) fn settle_pass_sale(&self, token: &AlkaneId, count: u128, cost: u128) -> Result<()> {
+    if !self.mint_proceeds_escrowed() {
+      return self.credit_treasury(token, cost);
+    }
+
+    self.pass_escrow_pointer(token).set_value::<u128>(self.pass_escrow(token) + cost);
+    self.escrowed_passes_pointer(token).set_value::<u128>(self.escrowed_passes(token) + count);
+
+    Ok(())
+  }
+
+  /// Account for a mint paid with a pass: while the guarantee is active and
+  /// sold passes remain in escrow, one pass's share of that escrow moves into
+  /// the mint escrow and the orbital becomes refundable like a paid mint.
+  /// Passes are fungible, so this draws on the escrow for giveaway passes too.
+  pub(crate) fn settle_pass_redemption(&self, index: u128) -> Result<()> {
+    if !self.mint_proceeds_escrowed() {
+      return Ok(());
+    }
+
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      let token = payment_token.get_id();
+      if self.escrowed_passes(&token) == 0 {
+        continue;
+      }
+
+      let price = self.take_pass_escrow(&token, 1);
+      self.mint_escrow_pointer(&token).set_value::<u128>(self.mint_escrow(&token) + price);
+
+      return self.record_escrowed_mint(index, &MintReceipt {
+        token,
+        price,
+        referrer: AlkaneId { block: 0, tx: 0 },
+        referral: 0,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Record the refundable price of an orbital whose proceeds are already in
+  /// the mint escrow, and release the escrow if that meets the threshold
+  fn record_escrowed_mint(&self, index: u128, receipt: &MintReceipt) -> Result<()> {
+    self.mint_receipt_pointer(index).set(Arc::new(receipt.to_bytes()));
+
+    let escrowed_mints = self.escrowed_mints_pointer().get_value::<u128>() + 1;
+    self.escrowed_mints_pointer().set_value::<u128>(escrowed_mints);
+
+    self.release_mint_escrow()
+  }
+
+  /// Release escrowed mint and pass sale proceeds to the treasury once enough
+  /// paid mints are made before the deadline. Owner mints and giveaway passes
+  /// redeemed after the sold-pass escrow runs out do not count.
+  fn release_mint_escrow(&self) -> Result<()> {
+    if self.escrowed_mints_pointer().get_value::<u128>() < self.refund_threshold_pointer().get_value::<u128>() {
+      return Ok(());
+    }
+
+    self.refund_met_pointer().set_value::<u128>(1);
+
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      let token = payment_token.get_id();
+      let escrowed = self.mint_escrow(&token) + self.pass_escrow(&token);
+      self.mint_escrow_pointer(&token).set_value::<u128>(0);
+      self.pass_escrow_pointer(&token).set_value::<u128>(0);
+      self.escrowed_passes_pointer(&token).set_value::<u128>(0);
+      if escrowed > 0 {
+        self.credit_treasury(&token, escrowed)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Hold primary sale proceeds in escrow until `min_minted` paid mints are
+  /// made. Only paid mints count, not the total minted. If that has not
+  /// happened by `deadline_height`, minting closes and holders can return
+  /// orbitals for the price paid until the owner reclaims the escrow after
+  /// REFUND_WINDOW blocks. Must be set before minting starts.
+  /// Parameters: [min_minted, deadline_height]
+  pub(crate) fn set_refund_guarantee(&self, min_minted: u128, deadline_height: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if self.guarantee_state() != GuaranteeState::None {
+      return Err(anyhow!("A refund guarantee is already configured"));
+    }
+    if self.instances_count() > 0 {
+      return Err(anyhow!("A refund guarantee must be configured before minting starts"));
+    }
+    if min_minted == 0 || min_minted > self.max_mints() {
+      return Err(anyhow!("Refund threshold must be between 1 and {}", self.max_mints()));
+    }
+    if deadline_height <= self.height() as u128 {
+      return Err(anyhow!("Refund deadline must be in the future"));
+    }
+
+    self.refund_threshold_pointer().set_value::<u128>(min_minted);
+    self.refund_deadline_pointer().set_value::<u128>(deadline_height);
+
+    Ok(response)
+  }
+
+  /// Return a supplied orbital from a failed guaranteed sale and receive the
+  /// price recorded when it was minted. The orbital is burned.
+  pub(crate) fn claim_mint_refund(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    self.ensure_refunds_open()?;
+
+    let receipt = self.mint_receipt(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} has no refundable mint payment", index))?;
+
+    let orbital_id = self.incoming_orbital(index)?;
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: orbital_id, value: 1 }])?;
+
+    self.retire_orbital(index)?;
+    self.mint_receipt_pointer(index).set(Arc::new(Vec::new()));
+
+    let escrowed = receipt.price - receipt.referral;
+    self.mint_escrow_pointer(&receipt.token).set_value::<u128>(self.mint_escrow(&receipt.token) - escrowed);
+    if receipt.referral > 0 {
+      self.reclaim_pending_referral(&receipt.referrer, &receipt.token, receipt.referral)?;
+    }
+
+    response.alkanes.0.push(AlkaneTransfer { id: receipt.token, value: receipt.price });
+
+    Ok(response)
+  }
+
+  /// Return unredeemed mint passes bought with `token_type` from a failed
+  /// guaranteed sale and receive their share of the pass escrow. The passes
+  /// are burned.
+  /// Parameters: [token_type (0=frBTC, 1=BUSD)]
+  pub(crate) fn claim_pass_refund(&self, token_type: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    self.ensure_refunds_open()?;
+
+    let token = PaymentToken::from_token_type(token_type)?.get_id();
+    let pass_id = self.mint_pass_id()?.ok_or_else(|| anyhow!("No mint passes have been issued"))?;
+
+    let count = self.incoming_mint_passes()?;
+    if count == 0 {
+      return Err(anyhow!("Supply the mint passes to refund"));
+    }
+
+    let escrowed_passes = self.escrowed_passes(&token);
+    if count > escrowed_passes {
+      return Err(anyhow!("Only {} passes sold for this token remain refundable", escrowed_passes));
+    }
+
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: pass_id, value: count }])?;
+
+    self.redeem_mint_passes(count)?;
+    let refund = self.take_pass_escrow(&token, count);
+
+    response.alkanes.0.push(AlkaneTransfer { id: token, value: refund });
+
+    Ok(response)
+  }
+
+  /// Move escrow left unclaimed after the refund window to the treasury.
+  /// Referral rewards on mints that were never refunded become claimable.
+  pub(crate) fn reclaim_mint_escrow(&self) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.ensure_refunds_open()?;
+
+    let window_end = self.refund_deadline_pointer().get_value::<u128>() + REFUND_WINDOW;
+    if (self.height() as u128) < window_end {
+      return Err(anyhow!("Refunds stay open until block {}", window_end));
+    }
+
+    self.refund_reclaimed_pointer().set_value::<u128>(1);
+
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      let token = payment_token.get_id();
+      let escrowed = self.mint_escrow(&token) + self.pass_escrow(&token);
+      self.mint_escrow_pointer(&token).set_value::<u128>(0);
+      self.pass_escrow_pointer(&token).set_value::<u128>(0);
+      self.escrowed_passes_pointer(&token).set_value::<u128>(0);
+      if escrowed > 0 {
+        self.credit_treasury(&token, escrowed)?;
+      }
+    }
+
+    Ok(response)
+  }
+
+  /// Returns: [min_minted, deadline_height, state (0=none, 1=active, 2=met, 3=failed),
+  ///  paid_mints, frbtc_escrow, busd_escrow, frbtc_pass_escrow, busd_pass_escrow,
+  ///  frbtc_escrowed_passes, busd_escrowed_passes]
+  pub(crate) fn get_refund_guarantee(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let mut data = Vec::with_capacity(160);
+    data.extend_from_slice(&self.refund_threshold_pointer().get_value::<u128>().to_le_bytes());
+    data.extend_from_slice(&self.refund_deadline_pointer().get_value::<u128>().to_le_bytes());
+    data.extend_from_slice(&self.guarantee_state().as_u128().to_le_bytes());
+    data.extend_from_slice(&self.escrowed_mints_pointer().get_value::<u128>().to_le_bytes());
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      data.extend_from_slice(&self.mint_escrow(&payment_token.get_id()).to_le_bytes());
+    }
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      data.extend_from_slice(&self.pass_escrow(&payment_token.get_id()).to_le_bytes());
+    }
+    for payment_token in [PaymentToken::FrBTC, PaymentToken::BUSD] {
+      data.extend_from_slice(&self.escrowed_passes(&payment_token.get_id()).to_le_bytes());
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+
+  /// Returns: [token_block, token_tx, price, referrer_block, referrer_tx, referral],
+  /// empty if the orbital has no refundable payment
+  pub(crate) fn get_mint_receipt(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = match self.mint_receipt(index)? {
+      Some(receipt) => receipt.to_bytes(),
+      None => Vec::new(),
+    };
+
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pass_escrow_is_spent_exactly() {
+    // Three passes sold for 10 in total
+    let mut escrowed = 10;
+    let mut passes = 3;
+    let mut refunded = Vec::new();
+    while passes > 0 {
+      let share = pass_escrow_share(escrowed, passes, 1);
+      refunded.push(share);
+      escrowed -= share;
+      passes -= 1;
+    }
+
+    assert_eq!(refunded, vec![3, 3, 4]);
+    assert_eq!(escrowed, 0);
+  }
+
+  #[test]
+  fn pass_escrow_share_is_proportional() {
+    assert_eq!(pass_escrow_share(900, 9, 3), 300);
+    assert_eq!(pass_escrow_share(900, 9, 12), 900);
+  }
+}
@@ -0,0 +1,790 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::storage_set::StorageSet;
+use crate::{
+  alkane_id_from_bytes, alkane_id_to_bytes, PaymentToken, RoyaltyNFTCollection, BPS_DENOMINATOR,
+  ROYALTY_PERCENTAGE,
+};
+
+/// Version tag leading the GetRoyaltyInfo payload
+const ROYALTY_INFO_VERSION: u128 = 1;
+
+/// Upper bound on configured royalty recipients
+const MAX_ROYALTY_RECIPIENTS: u128 = 8;
+
+/// Upper bound on royalty schedule steps
+const MAX_ROYALTY_STEPS: u128 = 16;
+
+/// Metric the royalty schedule steps are evaluated against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoyaltyScheduleMode {
+  /// Always charge ROYALTY_PERCENTAGE
+  Flat,
+  /// Step down by blocks elapsed since the orbital was minted
+  BlocksSinceMint,
+  /// Step down by the number of royalty-paying resales of the orbital
+  ResaleCount,
+}
+
+impl RoyaltyScheduleMode {
+  fn from_u128(mode: u128) -> Result<RoyaltyScheduleMode> {
+    match mode {
+      0 => Ok(RoyaltyScheduleMode::Flat),
+      1 => Ok(RoyaltyScheduleMode::BlocksSinceMint),
+      2 => Ok(RoyaltyScheduleMode::ResaleCount),
+      _ => Err(anyhow!("Invalid royalty schedule mode. Use 0 for flat, 1 for blocks since mint, 2 for resale count")),
+    }
+  }
+
+  fn as_u128(&self) -> u128 {
+    match self {
+      RoyaltyScheduleMode::Flat => 0,
+      RoyaltyScheduleMode::BlocksSinceMint => 1,
+      RoyaltyScheduleMode::ResaleCount => 2,
+    }
+  }
+}
+
+/// Royalty rate applying once the schedule metric reaches `threshold`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoyaltyStep {
+  pub threshold: u128,
+  pub royalty_bps: u128,
+}
+
+/// Royalty terms overriding the collection defaults for a single orbital
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoyaltyOverride {
+  pub royalty_bps: u128,
+  pub recipient: AlkaneId,
+}
+
+/// A partner marketplace collecting royalties on the collection's behalf
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Marketplace {
+  pub id: AlkaneId,
+  /// Reduced royalty rate charged on sales through this marketplace, if any
+  pub royalty_bps: Option<u128>,
+}
+
+/// A royalty payee and its share of the royalty, in basis points of the royalty amount
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoyaltyRecipient {
+  pub id: AlkaneId,
+  pub share_bps: u128,
+}
+
+impl RoyaltyNFTCollection {
+  fn royalty_recipients_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/royalty/recipients")
+  }
+
+  fn royalty_recipients_count(&self) -> u128 {
+    self.royalty_recipients_pointer().get_value::<u128>()
+  }
+
+  /// Royalty recipients configured by the owner, in insertion order
+  fn configured_royalty_recipients(&self) -> Result<Vec<RoyaltyRecipient>> {
+    let count = self.royalty_recipients_count();
+
+    let mut recipients = Vec::new();
+    for i in 0..count {
+      let bytes = self.royalty_recipients_pointer().select(&i.to_le_bytes().to_vec()).get();
+      if bytes.len() != 48 {
+        return Err(anyhow!("Invalid royalty recipient data length"));
+      }
+
+      recipients.push(RoyaltyRecipient {
+        id: alkane_id_from_bytes(&bytes[..32])?,
+        share_bps: u128::from_le_bytes(bytes[32..48].try_into().unwrap()),
+      });
+    }
+
+    Ok(recipients)
+  }
+
+  /// Full royalty split. Any share not assigned to a configured recipient
+  /// is reported as going to the collection itself.
+  pub(crate) fn royalty_recipients(&self) -> Result<Vec<RoyaltyRecipient>> {
+    let context = self.context()?;
+    let mut recipients = self.configured_royalty_recipients()?;
+
+    let assigned_bps: u128 = recipients.iter().map(|recipient| recipient.share_bps).sum();
+    if assigned_bps < BPS_DENOMINATOR {
+      recipients.push(RoyaltyRecipient {
+        id: context.myself.clone(),
+        share_bps: BPS_DENOMINATOR - assigned_bps,
+      });
+    }
+
+    Ok(recipients)
+  }
+
+  /// Serialize royalty info as
+  /// [version, royalty_bps, recipient_count, (recipient_block, recipient_tx, share_bps)...]
+  pub(crate) fn encode_royalty_info(&self, royalty_bps: u128, recipients: &[RoyaltyRecipient]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(48 + recipients.len() * 48);
+    data.extend_from_slice(&ROYALTY_INFO_VERSION.to_le_bytes());
+    data.extend_from_slice(&royalty_bps.to_le_bytes());
+    data.extend_from_slice(&(recipients.len() as u128).to_le_bytes());
+
+    for recipient in recipients {
+      data.extend_from_slice(&alkane_id_to_bytes(&recipient.id));
+      data.extend_from_slice(&recipient.share_bps.to_le_bytes());
+    }
+
+    data
+  }
+
+  fn royalty_schedule_mode_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/royalty/schedule/mode")
+  }
+
+  fn royalty_steps_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/royalty/schedule/steps")
+  }
+
+  fn royalty_schedule_mode(&self) -> Result<RoyaltyScheduleMode> {
+    RoyaltyScheduleMode::from_u128(self.royalty_schedule_mode_pointer().get_value::<u128>())
+  }
+
+  fn royalty_steps(&self) -> Result<Vec<RoyaltyStep>> {
+    let count = self.royalty_steps_pointer().get_value::<u128>();
+
+    let mut steps = Vec::new();
+    for i in 0..count {
+      let bytes = self.royalty_steps_pointer().select(&i.to_le_bytes().to_vec()).get();
+      if bytes.len() != 32 {
+        return Err(anyhow!("Invalid royalty step data length"));
+      }
+
+      steps.push(RoyaltyStep {
+        threshold: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+        royalty_bps: u128::from_le_bytes(bytes[16..32].try_into().unwrap()),
+      });
+    }
+
+    Ok(steps)
+  }
+
+  fn resale_count_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/royalty/resales/").select(&index.to_le_bytes().to_vec())
+  }
+
+  /// Number of royalty deposits made by the orbital at `index`, one per resale
+  pub(crate) fn resale_count(&self, index: u128) -> u128 {
+    self.resale_count_pointer(index).get_value::<u128>()
+  }
+
+  fn royalty_override_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/royalty/overrides/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn royalty_override_indices(&self) -> StorageSet {
+    StorageSet::new("/royalty/override-indices")
+  }
+
+  pub(crate) fn royalty_override(&self, index: u128) -> Result<Option<RoyaltyOverride>> {
+    let bytes = self.royalty_override_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+    if bytes.len() != 48 {
+      return Err(anyhow!("Invalid royalty override data length"));
+    }
+
+    Ok(Some(RoyaltyOverride {
+      royalty_bps: u128::from_le_bytes(bytes[..16].try_into().unwrap()),
+      recipient: alkane_id_from_bytes(&bytes[16..48])?,
+    }))
+  }
+
+  /// Royalty split for the orbital at `index`; an override pays its recipient in full
+  pub(crate) fn royalty_recipients_for(&self, index: u128) -> Result<Vec<RoyaltyRecipient>> {
+    match self.royalty_override(index)? {
+      Some(royalty_override) => Ok(vec![RoyaltyRecipient {
+        id: royalty_override.recipient,
+        share_bps: BPS_DENOMINATOR,
+      }]),
+      None => self.royalty_recipients(),
+    }
+  }
+
+  /// Royalty rate for the orbital at `index`: its override if set,
+  /// otherwise the current schedule
+  pub(crate) fn royalty_bps_for(&self, index: u128) -> Result<u128> {
+    if let Some(royalty_override) = self.royalty_override(index)? {
+      return Ok(royalty_override.royalty_bps);
+    }
+
+    self.scheduled_royalty_bps(Some(index))
+  }
+
+  /// Schedule rate for the orbital at `index`, or for a freshly minted,
+  /// never resold orbital when no index is given
+  fn scheduled_royalty_bps(&self, index: Option<u128>) -> Result<u128> {
+    let metric = match (self.royalty_schedule_mode()?, index) {
+      (RoyaltyScheduleMode::Flat, _) => return Ok(ROYALTY_PERCENTAGE),
+      (RoyaltyScheduleMode::BlocksSinceMint, Some(index)) => {
+        (self.height() as u128).saturating_sub(self.mint_height(index))
+      }
+      (RoyaltyScheduleMode::ResaleCount, Some(index)) => self.resale_count(index),
+      (_, None) => 0,
+    };
+
+    Ok(scheduled_bps(&self.royalty_steps()?, metric))
+  }
+
+  fn marketplace_pointer(&self, id: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/royalty/marketplaces/").select(&alkane_id_to_bytes(id))
+  }
+
+  fn marketplace_ids(&self) -> StorageSet {
+    StorageSet::new("/royalty/marketplace-ids")
+  }
+
+  pub(crate) fn marketplace(&self, id: &AlkaneId) -> Result<Option<Marketplace>> {
+    if !self.marketplace_ids().contains(&alkane_id_to_bytes(id)) {
+      return Ok(None);
+    }
+
+    let bytes = self.marketplace_pointer(id).get();
+    if bytes.len() != 32 {
+      return Err(anyhow!("Invalid marketplace data length"));
+    }
+
+    let has_custom_rate = u128::from_le_bytes(bytes[..16].try_into().unwrap()) != 0;
+    let royalty_bps = u128::from_le_bytes(bytes[16..32].try_into().unwrap());
+
+    Ok(Some(Marketplace {
+      id: id.clone(),
+      royalty_bps: if has_custom_rate { Some(royalty_bps) } else { None },
+    }))
+  }
+
+  /// Royalty rate for a sale of the orbital at `index` routed through
+  /// `marketplace`; allowlisted marketplaces never pay more than their custom rate
+  pub(crate) fn royalty_bps_via(&self, index: u128, marketplace: &AlkaneId) -> Result<u128> {
+    let royalty_bps = self.royalty_bps_for(index)?;
+
+    match self.marketplace(marketplace)?.and_then(|marketplace| marketplace.royalty_bps) {
+      Some(marketplace_bps) => Ok(royalty_bps.min(marketplace_bps)),
+      None => Ok(royalty_bps),
+    }
+  }
+
+  /// Royalty info for the calling orbital, including its override if set.
+  /// Any other caller gets the collection split at the rate a new orbital
+  /// starts at.
+  pub(crate) fn get_royalty_info(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    response.data = match self.orbital_index(&context.caller) {
+      Some(index) => self.encode_royalty_info(self.royalty_bps_for(index)?, &self.royalty_recipients_for(index)?),
+      None => self.encode_royalty_info(self.scheduled_royalty_bps(None)?, &self.royalty_recipients()?),
+    };
+
+    Ok(response)
+  }
+
+  pub(crate) fn get_orbital_royalty_info(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.lookup_instance(index)?;
+
+    let recipients = self.royalty_recipients_for(index)?;
+    let royalty_bps = self.royalty_bps_via(index, &context.caller)?;
+    response.data = self.encode_royalty_info(royalty_bps, &recipients);
+
+    Ok(response)
+  }
+
+  /// Quote the royalty owed on a sale of the orbital at `index`, at the
+  /// calling marketplace's rate when it is allowlisted
+  /// Returns: [royalty_bps, royalty_amount]
+  pub(crate) fn quote_royalty(&self, index: u128, sale_price: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.lookup_instance(index)?;
+
+    let royalty_bps = self.royalty_bps_via(index, &context.caller)?;
+    let royalty_amount = sale_price.checked_mul(royalty_bps)
+      .ok_or_else(|| anyhow!("sale price too large to quote"))?
+      / BPS_DENOMINATOR;
+
+    let mut data = Vec::with_capacity(32);
+    data.extend_from_slice(&royalty_bps.to_le_bytes());
+    data.extend_from_slice(&royalty_amount.to_le_bytes());
+
+    response.data = data;
+    Ok(response)
+  }
+
+  /// Select the schedule metric and drop any existing steps
+  /// Parameters: [mode (0=flat, 1=blocks since mint, 2=resale count)]
+  pub(crate) fn set_royalty_schedule(&self, mode: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.apply_royalty_schedule(mode)?;
+
+    Ok(response)
+  }
+
+  pub(crate) fn apply_royalty_schedule(&self, mode: u128) -> Result<()> {
+    let mode = RoyaltyScheduleMode::from_u128(mode)?;
+    self.royalty_schedule_mode_pointer().set_value::<u128>(mode.as_u128());
+    self.royalty_steps_pointer().set_value::<u128>(0);
+
+    Ok(())
+  }
+
+  /// Append a schedule step; thresholds must be strictly increasing
+  /// Parameters: [threshold, royalty_bps]
+  pub(crate) fn add_royalty_step(&self, threshold: u128, royalty_bps: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.apply_royalty_step(threshold, royalty_bps)?;
+
+    Ok(response)
+  }
+
+  pub(crate) fn apply_royalty_step(&self, threshold: u128, royalty_bps: u128) -> Result<()> {
+    if self.royalty_schedule_mode()? == RoyaltyScheduleMode::Flat {
+      return Err(anyhow!("Set a non-flat royalty schedule mode before adding steps"));
+    }
+
+    if royalty_bps > BPS_DENOMINATOR {
+      return Err(anyhow!("Royalty rate cannot exceed {} bps", BPS_DENOMINATOR));
+    }
+
+    let steps = self.royalty_steps()?;
+    let count = steps.len() as u128;
+    if count >= MAX_ROYALTY_STEPS {
+      return Err(anyhow!("At most {} royalty steps can be configured", MAX_ROYALTY_STEPS));
+    }
+
+    if let Some(last) = steps.last() {
+      if threshold <= last.threshold {
+        return Err(anyhow!("Royalty step thresholds must be strictly increasing"));
+      }
+    }
+
+    let mut bytes = Vec::with_capacity(32);
+    bytes.extend_from_slice(&threshold.to_le_bytes());
+    bytes.extend_from_slice(&royalty_bps.to_le_bytes());
+
+    self.royalty_steps_pointer()
+      .select(&count.to_le_bytes().to_vec())
+      .set(Arc::new(bytes));
+    self.royalty_steps_pointer().set_value::<u128>(count + 1);
+
+    Ok(())
+  }
+
+  /// Returns: [mode, step_count, (threshold, royalty_bps)...]
+  pub(crate) fn get_royalty_schedule(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let steps = self.royalty_steps()?;
+
+    let mut data = Vec::with_capacity(32 + steps.len() * 32);
+    data.extend_from_slice(&self.royalty_schedule_mode()?.as_u128().to_le_bytes());
+    data.extend_from_slice(&(steps.len() as u128).to_le_bytes());
+    for step in steps {
+      data.extend_from_slice(&step.threshold.to_le_bytes());
+      data.extend_from_slice(&step.royalty_bps.to_le_bytes());
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+
+  /// Give the orbital at `index` its own royalty rate and recipient
+  /// Parameters: [index, royalty_bps, recipient_block, recipient_tx]
+  pub(crate) fn set_royalty_override(
+    &self,
+    index: u128,
+    royalty_bps: u128,
+    recipient_block: u128,
+    recipient_tx: u128,
+  ) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if index >= self.index_limit() {
+      return Err(anyhow!("Orbital index {} is outside the collection", index));
+    }
+
+    if royalty_bps > BPS_DENOMINATOR {
+      return Err(anyhow!("Royalty rate cannot exceed {} bps", BPS_DENOMINATOR));
+    }
+
+    let recipient = AlkaneId { block: recipient_block, tx: recipient_tx };
+
+    let mut bytes = Vec::with_capacity(48);
+    bytes.extend_from_slice(&royalty_bps.to_le_bytes());
+    bytes.extend_from_slice(&alkane_id_to_bytes(&recipient));
+    self.royalty_override_pointer(index).set(Arc::new(bytes));
+
+    self.royalty_override_indices().insert(&index.to_le_bytes());
+
+    Ok(response)
+  }
+
+  /// Return the orbital at `index` to the collection royalty terms
+  pub(crate) fn clear_royalty_override(&self, index: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if !self.royalty_override_indices().contains(&index.to_le_bytes()) {
+      return Err(anyhow!("Orbital #{} has no royalty override", index));
+    }
+
+    self.royalty_override_indices().remove(&index.to_le_bytes());
+    self.royalty_override_pointer(index).set(Arc::new(Vec::new()));
+
+    Ok(response)
+  }
+
+  /// Returns: [count, (index, royalty_bps, recipient_block, recipient_tx)...]
+  pub(crate) fn get_royalty_overrides(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let indices = self.royalty_override_indices();
+    let count = indices.len();
+
+    let mut data = Vec::with_capacity(16 + count as usize * 64);
+    data.extend_from_slice(&count.to_le_bytes());
+    for key in indices.page(0, count) {
+      let index = u128::from_le_bytes(key[..16].try_into().unwrap());
+      if let Some(royalty_override) = self.royalty_override(index)? {
+        data.extend_from_slice(&index.to_le_bytes());
+        data.extend_from_slice(&royalty_override.royalty_bps.to_le_bytes());
+        data.extend_from_slice(&alkane_id_to_bytes(&royalty_override.recipient));
+      }
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+
+  /// Allowlist a marketplace, optionally with a reduced royalty rate
+  /// Parameters: [marketplace_block, marketplace_tx, has_custom_rate (0/1), royalty_bps]
+  pub(crate) fn register_marketplace(
+    &self,
+    marketplace_block: u128,
+    marketplace_tx: u128,
+    has_custom_rate: u128,
+    royalty_bps: u128,
+  ) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if has_custom_rate > 1 {
+      return Err(anyhow!("has_custom_rate must be 0 or 1"));
+    }
+
+    if royalty_bps > BPS_DENOMINATOR {
+      return Err(anyhow!("Royalty rate cannot exceed {} bps", BPS_DENOMINATOR));
+    }
+
+    let marketplace = AlkaneId { block: marketplace_block, tx: marketplace_tx };
+
+    let mut bytes = Vec::with_capacity(32);
+    bytes.extend_from_slice(&has_custom_rate.to_le_bytes());
+    bytes.extend_from_slice(&royalty_bps.to_le_bytes());
+    self.marketplace_pointer(&marketplace).set(Arc::new(bytes));
+
+    self.marketplace_ids().insert(&alkane_id_to_bytes(&marketplace));
+
+    Ok(response)
+  }
+
+  pub(crate) fn remove_marketplace(&self, marketplace_block: u128, marketplace_tx: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    let marketplace = AlkaneId { block: marketplace_block, tx: marketplace_tx };
+    if !self.marketplace_ids().contains(&alkane_id_to_bytes(&marketplace)) {
+      return Err(anyhow!("Marketplace {}:{} is not registered", marketplace.block, marketplace.tx));
+    }
+
+    self.marketplace_ids().remove(&alkane_id_to_bytes(&marketplace));
+    self.marketplace_pointer(&marketplace).set(Arc::new(Vec::new()));
+
+    Ok(response)
+  }
+
+  /// Returns: [count, (marketplace_block, marketplace_tx, has_custom_rate, royalty_bps)...]
+  pub(crate) fn get_marketplaces(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let ids = self.marketplace_ids();
+    let count = ids.len();
+
+    let mut data = Vec::with_capacity(16 + count as usize * 64);
+    data.extend_from_slice(&count.to_le_bytes());
+    for key in ids.page(0, count) {
+      if let Some(marketplace) = self.marketplace(&alkane_id_from_bytes(&key)?)? {
+        data.extend_from_slice(&alkane_id_to_bytes(&marketplace.id));
+        data.extend_from_slice(&(marketplace.royalty_bps.is_some() as u128).to_le_bytes());
+        data.extend_from_slice(&marketplace.royalty_bps.unwrap_or(0).to_le_bytes());
+      }
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+
+  /// Add a royalty recipient receiving `share_bps` of every royalty payment
+  /// Parameters: [recipient_block, recipient_tx, share_bps]
+  pub(crate) fn add_royalty_recipient(
+    &self,
+    recipient_block: u128,
+    recipient_tx: u128,
+    share_bps: u128,
+  ) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    if share_bps == 0 {
+      return Err(anyhow!("Royalty share must be greater than zero"));
+    }
+
+    let count = self.royalty_recipients_count();
+    if count >= MAX_ROYALTY_RECIPIENTS {
+      return Err(anyhow!("At most {} royalty recipients can be configured", MAX_ROYALTY_RECIPIENTS));
+    }
+
+    let assigned_bps: u128 = self.configured_royalty_recipients()?
+      .iter()
+      .map(|recipient| recipient.share_bps)
+      .sum();
+
+    if share_bps > BPS_DENOMINATOR - assigned_bps {
+      return Err(anyhow!(
+        "Royalty shares exceed 100%: {} bps already assigned, {} bps requested",
+        assigned_bps, share_bps
+      ));
+    }
+
+    let recipient_id = AlkaneId { block: recipient_block, tx: recipient_tx };
+
+    let mut bytes = alkane_id_to_bytes(&recipient_id);
+    bytes.extend_from_slice(&share_bps.to_le_bytes());
+
+    self.royalty_recipients_pointer()
+      .select(&count.to_le_bytes().to_vec())
+      .set(Arc::new(bytes));
+    self.royalty_recipients_pointer().set_value::<u128>(count + 1);
+
+    Ok(response)
+  }
+
+  /// Remove all configured recipients so royalties go to the collection again
+  pub(crate) fn clear_royalty_recipients(&self) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.royalty_recipients_pointer().set_value::<u128>(0);
+
+    Ok(response)
+  }
+
+  fn orbital_royalty_income_pointer(&self, index: u128, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/royalty/income/")
+      .select(&index.to_le_bytes().to_vec())
+      .select(&alkane_id_to_bytes(token))
+  }
+
+  /// Cumulative royalty income of `token` deposited by the orbital at `index`
+  pub(crate) fn orbital_royalty_income(&self, index: u128, token: &AlkaneId) -> u128 {
+    self.orbital_royalty_income_pointer(index, token).get_value::<u128>()
+  }
+
+  /// Split a royalty payment for the orbital at `index` among its royalty
+  /// recipients. Other recipients are credited claimable proceeds
+  /// (ClaimSaleProceeds); the collection's share and any rounding dust go to
+  /// the treasury, less the share distributed to stakers.
+  pub(crate) fn record_royalty_income(&self, index: u128, token: &AlkaneId, amount: u128) -> Result<()> {
+    let context = self.context()?;
+
+    if PaymentToken::from_alkane_id(token).is_none() {
+      return Err(anyhow!("Royalties are only accepted in frBTC or BUSD"));
+    }
+
+    let (payouts, collection_share) = split_royalty(amount, &self.royalty_recipients_for(index)?, &context.myself)?;
+    for (recipient, share) in payouts {
+      self.credit_sale_proceeds(&recipient, token, share)?;
+    }
+
+    let staking_reward = self.distribute_staking_reward(token, collection_share)?;
+    self.credit_treasury(token, collection_share - staking_reward)?;
+
+    let income = self.orbital_royalty_income(index, token)
+      .checked_add(amount)
+      .ok_or_else(|| anyhow!("royalty income overflow"))?;
+    self.orbital_royalty_income_pointer(index, token).set_value::<u128>(income);
+
+    Ok(())
+  }
+
+  /// Record a completed resale of the orbital at `index`
+  pub(crate) fn record_resale(&self, index: u128) {
+    self.resale_count_pointer(index).set_value::<u128>(self.resale_count(index) + 1);
+  }
+
+  /// Receive secondary-sale royalties from a child orbital
+  /// Only the orbital registered at `index` may deposit on its own behalf
+  pub(crate) fn deposit_royalty(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let orbital_id = self.lookup_instance(index)?;
+    if context.caller != orbital_id {
+      return Err(anyhow!("Royalty deposits are only accepted from orbital #{}", index));
+    }
+
+    if context.incoming_alkanes.0.is_empty() {
+      return Err(anyhow!("No royalty payment provided"));
+    }
+
+    for transfer in &context.incoming_alkanes.0 {
+      self.record_royalty_income(index, &transfer.id, transfer.value)?;
+    }
+
+    // Each deposit settles one resale, which drives resale-count royalty schedules
+    self.record_resale(index);
+
+    // Deposited royalties stay with the collection
+    Ok(CallResponse::default())
+  }
+
+  pub(crate) fn get_orbital_royalty_income(
+    &self,
+    index: u128,
+    token_block: u128,
+    token_tx: u128,
+  ) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let token = AlkaneId { block: token_block, tx: token_tx };
+    response.data = self.orbital_royalty_income(index, &token).to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+}
+
+/// Shares of a royalty `amount` owed to each recipient other than the
+/// collection, and what remains with the collection. Shares round down, so
+/// rounding dust stays with the collection.
+fn split_royalty(
+  amount: u128,
+  recipients: &[RoyaltyRecipient],
+  myself: &AlkaneId,
+) -> Result<(Vec<(AlkaneId, u128)>, u128)> {
+  let mut payouts = Vec::new();
+  let mut collection_share = amount;
+  for recipient in recipients.iter().filter(|recipient| recipient.id != *myself) {
+    let share = amount.checked_mul(recipient.share_bps)
+      .ok_or_else(|| anyhow!("royalty amount too large to split"))?
+      / BPS_DENOMINATOR;
+    payouts.push((recipient.id, share));
+    collection_share -= share;
+  }
+
+  Ok((payouts, collection_share))
+}
+
+/// Rate reached at `metric` by steps stored in ascending threshold order;
+/// the last step reached wins and the flat rate applies below the first
+fn scheduled_bps(steps: &[RoyaltyStep], metric: u128) -> u128 {
+  let mut royalty_bps = ROYALTY_PERCENTAGE;
+  for step in steps {
+    if metric < step.threshold {
+      break;
+    }
+    royalty_bps = step.royalty_bps;
+  }
+
+  royalty_bps
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn steps() -> Vec<RoyaltyStep> {
+    vec![
+      RoyaltyStep { threshold: 100, royalty_bps: 400 },
+      RoyaltyStep { threshold: 1_000, royalty_bps: 250 },
+    ]
+  }
+
+  #[test]
+  fn flat_rate_applies_below_first_step() {
+    assert_eq!(scheduled_bps(&steps(), 99), ROYALTY_PERCENTAGE);
+    assert_eq!(scheduled_bps(&[], 5_000), ROYALTY_PERCENTAGE);
+  }
+
+  #[test]
+  fn step_applies_from_its_threshold() {
+    assert_eq!(scheduled_bps(&steps(), 100), 400);
+    assert_eq!(scheduled_bps(&steps(), 999), 400);
+    assert_eq!(scheduled_bps(&steps(), 1_000), 250);
+    assert_eq!(scheduled_bps(&steps(), u128::MAX), 250);
+  }
+
+  #[test]
+  fn royalty_split_pays_recipients_and_keeps_the_rest() {
+    let myself = AlkaneId { block: 2, tx: 10 };
+    let artist = AlkaneId { block: 2, tx: 11 };
+    let curator = AlkaneId { block: 2, tx: 12 };
+    let recipients = [
+      RoyaltyRecipient { id: artist, share_bps: 6_000 },
+      RoyaltyRecipient { id: curator, share_bps: 3_333 },
+      RoyaltyRecipient { id: myself, share_bps: 667 },
+    ];
+
+    let (payouts, collection_share) = split_royalty(1_001, &recipients, &myself).unwrap();
+
+    assert_eq!(payouts, vec![(artist, 600), (curator, 333)]);
+    assert_eq!(collection_share, 68);
+  }
+
+  #[test]
+  fn royalty_split_without_recipients_stays_with_collection() {
+    let myself = AlkaneId { block: 2, tx: 10 };
+    assert_eq!(split_royalty(500, &[], &myself).unwrap(), (Vec::new(), 500));
+  }
+}
@@ -0,0 +1,308 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, parcel::AlkaneTransfer, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::receipt::ReceiptKind;
+use crate::storage_set::StorageSet;
+use crate::{alkane_id_from_bytes, alkane_id_to_bytes, forward_except, RoyaltyNFTCollection, BPS_DENOMINATOR};
+
+/// Fixed-point scale of the reward-per-share accumulator
+const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+impl RoyaltyNFTCollection {
+  fn staking_share_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/staking/share-bps")
+  }
+
+  fn total_staked_pointer(&self) -> StoragePointer {
+    StoragePointer::from_keyword("/staking/total")
+  }
+
+  /// Stake receipt issued for the orbital at `index`
+  fn staker_pointer(&self, index: u128) -> StoragePointer {
+    StoragePointer::from_keyword("/staking/stakers/").select(&index.to_le_bytes().to_vec())
+  }
+
+  fn reward_per_share_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/staking/reward-per-share/").select(&alkane_id_to_bytes(token))
+  }
+
+  fn reward_debt_pointer(&self, index: u128, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/staking/reward-debt/")
+      .select(&index.to_le_bytes().to_vec())
+      .select(&alkane_id_to_bytes(token))
+  }
+
+  /// Rewards allocated to stakers and not yet paid out, including the rounding
+  /// dust no staker can claim
+  fn staking_pool_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/staking/pool/").select(&alkane_id_to_bytes(token))
+  }
+
+  pub(crate) fn staking_pool(&self, token: &AlkaneId) -> u128 {
+    self.staking_pool_pointer(token).get_value::<u128>()
+  }
+
+  /// Every token that has ever been distributed to stakers
+  fn reward_tokens(&self) -> StorageSet {
+    StorageSet::new("/staking/reward-tokens")
+  }
+
+  fn reward_token_ids(&self) -> Result<Vec<AlkaneId>> {
+    let tokens = self.reward_tokens();
+    tokens.page(0, tokens.len())
+      .iter()
+      .map(|key| alkane_id_from_bytes(key))
+      .collect()
+  }
+
+  fn staking_share_bps(&self) -> u128 {
+    self.staking_share_pointer().get_value::<u128>()
+  }
+
+  fn total_staked(&self) -> u128 {
+    self.total_staked_pointer().get_value::<u128>()
+  }
+
+  pub(crate) fn staker(&self, index: u128) -> Result<Option<AlkaneId>> {
+    let bytes = self.staker_pointer(index).get();
+    if bytes.is_empty() {
+      return Ok(None);
+    }
+
+    Ok(Some(alkane_id_from_bytes(&bytes)?))
+  }
+
+  /// Allocate the stakers' share of a royalty payment and return the amount
+  /// taken for the staking pool
+  pub(crate) fn distribute_staking_reward(&self, token: &AlkaneId, amount: u128) -> Result<u128> {
+    let total_staked = self.total_staked();
+    if total_staked == 0 {
+      return Ok(0);
+    }
+
+    let reward = amount.checked_mul(self.staking_share_bps())
+      .ok_or_else(|| anyhow!("royalty amount too large to distribute"))?
+      / BPS_DENOMINATOR;
+
+    let increment = reward_increment(reward, total_staked)?;
+    if increment == 0 {
+      return Ok(0);
+    }
+
+    self.reward_tokens().insert(&alkane_id_to_bytes(token));
+
+    let reward_per_share = self.reward_per_share_pointer(token).get_value::<u128>()
+      .checked_add(increment)
+      .ok_or_else(|| anyhow!("reward per share overflow"))?;
+    self.reward_per_share_pointer(token).set_value::<u128>(reward_per_share);
+
+    // The whole reward leaves the treasury; rounding dust stays in the pool
+    let pool = self.staking_pool(token)
+      .checked_add(reward)
+      .ok_or_else(|| anyhow!("staking pool overflow"))?;
+    self.staking_pool_pointer(token).set_value::<u128>(pool);
+
+    Ok(reward)
+  }
+
+  fn pending_reward(&self, index: u128, token: &AlkaneId) -> u128 {
+    let reward_per_share = self.reward_per_share_pointer(token).get_value::<u128>();
+    let reward_debt = self.reward_debt_pointer(index, token).get_value::<u128>();
+
+    (reward_per_share - reward_debt) / REWARD_PRECISION
+  }
+
+  /// Pay out everything the staked orbital has accrued and reset its debt
+  fn harvest(&self, index: u128, response: &mut CallResponse) -> Result<()> {
+    for token in self.reward_token_ids()? {
+      let reward = self.pending_reward(index, &token);
+
+      let reward_per_share = self.reward_per_share_pointer(&token).get_value::<u128>();
+      self.reward_debt_pointer(index, &token).set_value::<u128>(reward_per_share);
+
+      if reward > 0 {
+        let pool = self.staking_pool(&token)
+          .checked_sub(reward)
+          .ok_or_else(|| anyhow!("staking reward exceeds the staking pool"))?;
+        self.staking_pool_pointer(&token).set_value::<u128>(pool);
+
+        response.alkanes.0.push(AlkaneTransfer { id: token, value: reward });
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Check the call carries the stake receipt of the orbital at `index` and
+  /// return what to keep to retire it
+  fn take_stake_receipt(&self, index: u128) -> Result<Vec<AlkaneTransfer>> {
+    let receipt = self.staker(index)?
+      .ok_or_else(|| anyhow!("Orbital #{} is not staked", index))?;
+
+    self.take_receipt(&receipt)
+  }
+
+  /// Escrow the supplied orbital to earn a share of royalty income, returning
+  /// the stake receipt that claims rewards and unstakes it
+  pub(crate) fn stake(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let orbital_id = self.incoming_orbital(index)?;
+    let mut response = forward_except(&context.incoming_alkanes, &[AlkaneTransfer { id: orbital_id, value: 1 }])?;
+
+    // Start accruing from the current accumulator values
+    for token in self.reward_token_ids()? {
+      let reward_per_share = self.reward_per_share_pointer(&token).get_value::<u128>();
+      self.reward_debt_pointer(index, &token).set_value::<u128>(reward_per_share);
+    }
+
+    let receipt = self.issue_receipt(ReceiptKind::Stake)?;
+    self.staker_pointer(index).set(Arc::new(alkane_id_to_bytes(&receipt.id)));
+    self.total_staked_pointer().set_value::<u128>(self.total_staked() + 1);
+
+    response.alkanes.0.push(receipt);
+
+    Ok(response)
+  }
+
+  /// Return the stake receipt to claim accrued rewards and withdraw the
+  /// staked orbital
+  pub(crate) fn unstake(&self, index: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+
+    let mut response = forward_except(&context.incoming_alkanes, &self.take_stake_receipt(index)?)?;
+
+    self.harvest(index, &mut response)?;
+
+    self.staker_pointer(index).set(Arc::new(Vec::new()));
+    self.total_staked_pointer().set_value::<u128>(self.total_staked() - 1);
+
+    response.alkanes.0.push(AlkaneTransfer {
+      id: self.lookup_instance(index)?,
+      value: 1,
+    });
+
+    Ok(response)
+  }
+
+  /// Claim accrued rewards, leaving the orbital staked. The stake receipt
+  /// must be supplied and is returned.
+  pub(crate) fn claim_staking_rewards(&self, index: u128) -> Result<CallResponse> {
+    self.take_stake_receipt(index)?;
+
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.harvest(index, &mut response)?;
+
+    Ok(response)
+  }
+
+  /// Set the fraction of royalty income shared with stakers
+  /// Parameters: [share_bps]
+  pub(crate) fn set_staking_share(&self, share_bps: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    self.apply_staking_share(share_bps)?;
+
+    Ok(response)
+  }
+
+  pub(crate) fn apply_staking_share(&self, share_bps: u128) -> Result<()> {
+    if share_bps > BPS_DENOMINATOR {
+      return Err(anyhow!("Staking share cannot exceed {} bps", BPS_DENOMINATOR));
+    }
+
+    self.staking_share_pointer().set_value::<u128>(share_bps);
+
+    Ok(())
+  }
+
+  pub(crate) fn get_pending_rewards(&self, index: u128, token_block: u128, token_tx: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let token = AlkaneId { block: token_block, tx: token_tx };
+    let pending = if self.staker(index)?.is_some() {
+      self.pending_reward(index, &token)
+    } else {
+      0
+    };
+
+    response.data = pending.to_le_bytes().to_vec();
+    Ok(response)
+  }
+
+  /// Returns: [share_bps, total_staked, reward_token_count, (token_block, token_tx)...]
+  pub(crate) fn get_staking_info(&self) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let tokens = self.reward_token_ids()?;
+
+    let mut data = Vec::with_capacity(48 + tokens.len() * 32);
+    data.extend_from_slice(&self.staking_share_bps().to_le_bytes());
+    data.extend_from_slice(&self.total_staked().to_le_bytes());
+    data.extend_from_slice(&(tokens.len() as u128).to_le_bytes());
+    for token in tokens {
+      data.extend_from_slice(&alkane_id_to_bytes(&token));
+    }
+
+    response.data = data;
+    Ok(response)
+  }
+}
+
+/// Accumulator increment per staked orbital for a reward shared by `total_staked` orbitals
+fn reward_increment(reward: u128, total_staked: u128) -> Result<u128> {
+  Ok(reward.checked_mul(REWARD_PRECISION)
+    .ok_or_else(|| anyhow!("royalty amount too large to distribute"))?
+    / total_staked)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn small_distributions_never_pay_out_more_than_deducted() {
+    // Stakers join between distributions, each starting from the current accumulator
+    let mut debts: Vec<u128> = vec![0, 0, 0];
+    let mut reward_per_share = 0u128;
+    let mut deducted = 0u128;
+
+    for (round, reward) in [1u128, 1, 2, 1, 5, 1, 1].into_iter().enumerate() {
+      let increment = reward_increment(reward, debts.len() as u128).unwrap();
+      if increment > 0 {
+        reward_per_share += increment;
+        deducted += reward;
+      }
+
+      let claimable: u128 = debts.iter()
+        .map(|debt| (reward_per_share - debt) / REWARD_PRECISION)
+        .sum();
+      assert!(claimable <= deducted, "round {}: {} claimable, {} deducted", round, claimable, deducted);
+
+      if round % 2 == 1 {
+        debts.push(reward_per_share);
+      }
+    }
+
+    assert!(deducted > 0);
+  }
+
+  #[test]
+  fn reward_too_small_to_share_has_no_increment() {
+    assert_eq!(reward_increment(0, 3).unwrap(), 0);
+    assert_eq!(reward_increment(1, REWARD_PRECISION + 1).unwrap(), 0);
+    assert_eq!(reward_increment(3, 3).unwrap(), REWARD_PRECISION);
+  }
+}
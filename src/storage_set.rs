@@ -0,0 +1,102 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::storage::StoragePointer;
+
+use std::sync::Arc;
+
+/// Unordered set of byte keys in contract storage, supporting constant-time
+/// insert and removal plus enumeration by slot for paginated queries
+pub struct StorageSet {
+  pointer: StoragePointer,
+}
+
+impl StorageSet {
+  pub fn new(keyword: &str) -> StorageSet {
+    StorageSet { pointer: StoragePointer::from_keyword(keyword) }
+  }
+
+  fn items(&self) -> StoragePointer {
+    self.pointer.keyword("/items")
+  }
+
+  /// 1-based slot of a key, 0 if absent
+  fn position(&self, key: &[u8]) -> StoragePointer {
+    self.pointer.keyword("/positions/").select(&key.to_vec())
+  }
+
+  fn slot(&self, slot: u128) -> StoragePointer {
+    self.items().select(&slot.to_le_bytes().to_vec())
+  }
+
+  pub fn len(&self) -> u128 {
+    self.items().get_value::<u128>()
+  }
+
+  pub fn contains(&self, key: &[u8]) -> bool {
+    self.position(key).get_value::<u128>() != 0
+  }
+
+  pub fn insert(&self, key: &[u8]) {
+    if self.contains(key) {
+      return;
+    }
+
+    let count = self.len();
+    self.slot(count).set(Arc::new(key.to_vec()));
+    self.position(key).set_value::<u128>(count + 1);
+    self.items().set_value::<u128>(count + 1);
+  }
+
+  /// Remove a key, moving the last entry into the freed slot
+  pub fn remove(&self, key: &[u8]) {
+    let position = self.position(key).get_value::<u128>();
+    if position == 0 {
+      return;
+    }
+
+    let last_slot = self.len() - 1;
+    let last_key = self.slot(last_slot).get();
+
+    self.slot(position - 1).set(last_key.clone());
+    self.position(&last_key).set_value::<u128>(position);
+    self.items().set_value::<u128>(last_slot);
+
+    self.position(key).set_value::<u128>(0);
+  }
+
+  /// Keys in slots [offset, offset + limit)
+  pub fn page(&self, offset: u128, limit: u128) -> Vec<Arc<Vec<u8>>> {
+    let end = self.len().min(offset.saturating_add(limit));
+
+    (offset..end).map(|slot| self.slot(slot).get()).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn remove_moves_last_key_into_freed_slot() {
+    let set = StorageSet::new("/test/storage-set/swap-remove");
+    for key in [b"a", b"b", b"c"] {
+      set.insert(key);
+    }
+    set.insert(b"b");
+    assert_eq!(set.len(), 3);
+
+    set.remove(b"a");
+
+    assert_eq!(set.len(), 2);
+    assert!(!set.contains(b"a"));
+    assert_eq!(
+      set.page(0, 10).iter().map(|key| key.as_slice().to_vec()).collect::<Vec<_>>(),
+      vec![b"c".to_vec(), b"b".to_vec()]
+    );
+
+    set.remove(b"b");
+    set.remove(b"b");
+    assert_eq!(set.len(), 1);
+    assert!(set.contains(b"c"));
+  }
+}
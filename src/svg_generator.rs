@@ -1,100 +1,281 @@
-use serde_json::json;
-use anyhow::Result;
+use serde_json::{json, Value};
+use anyhow::{anyhow, Result};
 
 pub struct SvgGenerator;
 
+/// Options controlling how an orbital's art is rendered
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderOptions {
+  /// Freeze animations at a representative frame so the SVG can be rasterized
+  /// by marketplaces and image proxies that strip SMIL
+  pub static_mode: bool,
+}
+
+/// Number of art styles an orbital can be rendered in
+pub const ART_STYLE_COUNT: usize = 10;
+
+/// Relative frequency of each art style among index-derived orbitals, in
+/// `ArtStyle` order. The default keeps the original six-style rotation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StyleWeights(pub [u128; ART_STYLE_COUNT]);
+
+impl Default for StyleWeights {
+  fn default() -> StyleWeights {
+    StyleWeights([1, 1, 1, 1, 1, 1, 0, 0, 0, 0])
+  }
+}
+
+impl StyleWeights {
+  pub fn total(&self) -> u128 {
+    self.0.iter().sum()
+  }
+
+  /// Art style for `index`, drawn in proportion to the weights
+  pub fn pick(&self, index: u128) -> u128 {
+    // Collections on the default weights keep the original index % 6 rotation
+    if *self == StyleWeights::default() {
+      return index % 6;
+    }
+
+    let mut slot = mix64(index as u64) as u128 % self.total();
+    for (style, weight) in self.0.iter().enumerate() {
+      if slot < *weight {
+        return style as u128;
+      }
+      slot -= weight;
+    }
+
+    0
+  }
+
+  pub fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(ART_STYLE_COUNT * 16);
+    for weight in self.0 {
+      bytes.extend_from_slice(&weight.to_le_bytes());
+    }
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<StyleWeights> {
+    if bytes.len() != ART_STYLE_COUNT * 16 {
+      return Err(anyhow!("Invalid style weights data length"));
+    }
+
+    let mut weights = [0u128; ART_STYLE_COUNT];
+    for (style, weight) in weights.iter_mut().enumerate() {
+      *weight = u128::from_le_bytes(bytes[style * 16..(style + 1) * 16].try_into().unwrap());
+    }
+
+    Ok(StyleWeights(weights))
+  }
+}
+
+/// SplitMix64 finalizer, used to scatter seeds
+fn mix64(value: u64) -> u64 {
+  let mut x = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+  x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  x ^ (x >> 31)
+}
+
+/// Deterministic value in [0, 1) for a seed and a pair of coordinates
+fn unit_hash(seed: u128, x: i64, y: i64) -> f64 {
+  let h = mix64(
+    (seed as u64)
+      ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+      ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F),
+  );
+  (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Numeric trait values an orbital is rendered from. Regular orbitals derive
+/// them from their index; fused orbitals carry traits inherited from their parents.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitalTraits {
+  pub art_style: u128,
+  pub color_palette: u128,
+  pub pattern_type: u128,
+  pub complexity: u128,
+  pub symmetry: u128,
+  pub energy_level: u128,
+}
+
+impl OrbitalTraits {
+  pub fn from_index(index: u128, style_weights: &StyleWeights) -> OrbitalTraits {
+    OrbitalTraits {
+      art_style: style_weights.pick(index),
+      color_palette: (index / 6) % 12,
+      pattern_type: (index / 72) % 6,
+      complexity: (index / 432) % 5,
+      symmetry: (index / 2160) % 4,
+      energy_level: (index / 8640) % 4,
+    }
+  }
+
+  pub fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(96);
+    for value in [
+      self.art_style, self.color_palette, self.pattern_type,
+      self.complexity, self.symmetry, self.energy_level,
+    ] {
+      bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<OrbitalTraits> {
+    if bytes.len() != 96 {
+      return Err(anyhow!("Invalid orbital traits data length"));
+    }
+
+    let field = |i: usize| u128::from_le_bytes(bytes[i * 16..(i + 1) * 16].try_into().unwrap());
+
+    Ok(OrbitalTraits {
+      art_style: field(0) % ART_STYLE_COUNT as u128,
+      color_palette: field(1) % 12,
+      pattern_type: field(2) % 6,
+      complexity: field(3) % 5,
+      symmetry: field(4) % 4,
+      energy_level: field(5) % 4,
+    })
+  }
+}
+
 impl SvgGenerator {
-  /// Generate algorithmic attributes based on NFT index
-  pub fn get_attributes(index: u128) -> Result<String> {
-    let art_style = Self::get_art_style(index);
-    let color_palette = Self::get_color_palette(index);
-    let pattern_type = Self::get_pattern_type(index);
-    let complexity = Self::get_complexity(index);
-    let symmetry = Self::get_symmetry(index);
-    let energy = Self::get_energy_level(index);
-
-    let attributes = json!({
-      "art_style": art_style,
-      "color_palette": color_palette,
-      "pattern_type": pattern_type,
-      "complexity": complexity,
-      "symmetry": symmetry,
-      "energy_level": energy,
-      "rarity_score": Self::calculate_rarity_score(index)
-    });
+  /// Generate algorithmic attributes from an orbital's traits
+  pub fn get_attributes(traits: &OrbitalTraits) -> Value {
+    json!({
+      "art_style": Self::get_art_style(traits),
+      "color_palette": Self::get_color_palette(traits),
+      "pattern_type": Self::get_pattern_type(traits),
+      "complexity": Self::get_complexity(traits),
+      "symmetry": Self::get_symmetry(traits),
+      "energy_level": Self::get_energy_level(traits),
+      "rarity_score": Self::calculate_rarity_score(traits),
+      "layers": Self::get_layers(traits)
+    })
+  }
 
-    Ok(attributes.to_string())
+  /// Layer and variant used for each layer of an orbital's art, bottom to top
+  fn get_layers(traits: &OrbitalTraits) -> Vec<Value> {
+    ORBITAL_LAYERS.iter()
+      .map(|layer| json!({
+        "layer": layer.name(),
+        "variant": layer.variant(traits)
+      }))
+      .collect()
   }
 
-  /// Generate algorithmic SVG art based on NFT index
-  pub fn generate_svg(index: u128) -> Result<String> {
+  /// Generate algorithmic SVG art from an orbital's traits, seeded by its index
+  pub fn generate_svg(index: u128, traits: &OrbitalTraits) -> Result<String> {
+    Self::generate_svg_with_options(index, traits, RenderOptions::default())
+  }
+
+  /// Generate an orbital's SVG art with explicit rendering options
+  pub fn generate_svg_with_options(index: u128, traits: &OrbitalTraits, options: RenderOptions) -> Result<String> {
     let mut svg = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
     svg.push_str("<svg width=\"100%\" height=\"100%\" viewBox=\"0 0 400 400\" xmlns=\"http://www.w3.org/2000/svg\">\n");
-    
-    // Add gradient definitions
-    svg.push_str(&Self::generate_gradients(index));
-    
-    // Generate background
-    svg.push_str(&Self::generate_background(index));
-    
-    // Generate main algorithmic pattern based on art style
-    match Self::get_art_style_enum(index) {
-      ArtStyle::GeometricFractal => svg.push_str(&Self::generate_fractal_pattern(index)),
-      ArtStyle::FlowField => svg.push_str(&Self::generate_flow_field(index)),
-      ArtStyle::CirclePacking => svg.push_str(&Self::generate_circle_packing(index)),
-      ArtStyle::Mandala => svg.push_str(&Self::generate_mandala(index)),
-      ArtStyle::WaveInterference => svg.push_str(&Self::generate_wave_pattern(index)),
-      ArtStyle::Crystalline => svg.push_str(&Self::generate_crystal_pattern(index)),
+    svg.push_str(&Self::render_layers(ORBITAL_LAYERS, &RenderContext { index, traits, id_suffix: "", options }));
+    svg.push_str("</svg>");
+    Ok(svg)
+  }
+
+  fn render_layers(layers: &[&dyn Layer], context: &RenderContext) -> String {
+    layers.iter().map(|layer| layer.render(context)).collect()
+  }
+
+  /// Generate a composite preview of a bundle, tiling each contained
+  /// orbital's art in a square grid
+  pub fn generate_bundle_svg(bundle_id: u128, pieces: &[(u128, OrbitalTraits)]) -> Result<String> {
+    if pieces.is_empty() {
+      return Err(anyhow!("Bundle #{} has no pieces to render", bundle_id));
     }
-    
-    // Add overlay effects
-    svg.push_str(&Self::generate_overlay_effects(index));
-    
-    // Add signature/index number
-    svg.push_str(&format!("<text x=\"20\" y=\"380\" font-family=\"monospace\" font-size=\"12\" fill=\"white\" opacity=\"0.6\">#{}</text>\n", index));
-    
+
+    let mut columns = 1;
+    while columns * columns < pieces.len() {
+      columns += 1;
+    }
+    let tile = 400.0 / columns as f64;
+
+    let mut svg = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    svg.push_str("<svg width=\"100%\" height=\"100%\" viewBox=\"0 0 400 400\" xmlns=\"http://www.w3.org/2000/svg\">\n");
+    svg.push_str(r#"<rect width="400" height="400" fill="black"/>"#);
+
+    for (position, (index, traits)) in pieces.iter().enumerate() {
+      let x = (position % columns) as f64 * tile;
+      let y = (position / columns) as f64 * tile;
+      // Each tile gets its own gradient ids so pieces keep their palettes
+      let id_suffix = format!("-{}", position);
+
+      svg.push_str(&format!(
+        r#"<svg x="{}" y="{}" width="{}" height="{}" viewBox="0 0 400 400">"#,
+        x, y, tile, tile
+      ));
+      svg.push_str(&Self::render_layers(BUNDLE_TILE_LAYERS, &RenderContext {
+        index: *index,
+        traits,
+        id_suffix: &id_suffix,
+        options: RenderOptions::default(),
+      }));
+      svg.push_str(&format!("<text x=\"20\" y=\"380\" font-family=\"monospace\" font-size=\"24\" fill=\"white\" opacity=\"0.8\">#{}</text>", index));
+      svg.push_str(r#"<rect width="400" height="400" fill="none" stroke="black" stroke-width="8"/></svg>"#);
+    }
+
+    svg.push_str(&format!("<text x=\"200\" y=\"395\" text-anchor=\"middle\" font-family=\"monospace\" font-size=\"12\" fill=\"white\">Bundle #{} - {} orbitals</text>\n", bundle_id, pieces.len()));
     svg.push_str("</svg>");
     Ok(svg)
   }
 
+  fn generate_pattern(index: u128, traits: &OrbitalTraits) -> String {
+    match Self::get_art_style_enum(traits) {
+      ArtStyle::GeometricFractal => Self::generate_fractal_pattern(index, traits),
+      ArtStyle::FlowField => Self::generate_flow_field(index, traits),
+      ArtStyle::CirclePacking => Self::generate_circle_packing(index, traits),
+      ArtStyle::Mandala => Self::generate_mandala(index, traits),
+      ArtStyle::WaveInterference => Self::generate_wave_pattern(index, traits),
+      ArtStyle::Crystalline => Self::generate_crystal_pattern(index, traits),
+      ArtStyle::Voronoi => Self::generate_voronoi(index, traits),
+      ArtStyle::TruchetTiles => Self::generate_truchet_tiles(index, traits),
+      ArtStyle::LSystem => Self::generate_lsystem(index, traits),
+      ArtStyle::NoiseField => Self::generate_noise_contours(index, traits),
+    }
+  }
+
   // Art Style Classification
-  fn get_art_style_enum(index: u128) -> ArtStyle {
-    match index % 6 {
+  fn get_art_style_enum(traits: &OrbitalTraits) -> ArtStyle {
+    match traits.art_style {
       0 => ArtStyle::GeometricFractal,
       1 => ArtStyle::FlowField,
       2 => ArtStyle::CirclePacking,
       3 => ArtStyle::Mandala,
       4 => ArtStyle::WaveInterference,
-      _ => ArtStyle::Crystalline,
+      5 => ArtStyle::Crystalline,
+      6 => ArtStyle::Voronoi,
+      7 => ArtStyle::TruchetTiles,
+      8 => ArtStyle::LSystem,
+      _ => ArtStyle::NoiseField,
     }
   }
 
-  fn get_art_style(index: u128) -> String {
-    match Self::get_art_style_enum(index) {
-      ArtStyle::GeometricFractal => "Geometric Fractal".to_string(),
-      ArtStyle::FlowField => "Flow Field".to_string(),
-      ArtStyle::CirclePacking => "Circle Packing".to_string(),
-      ArtStyle::Mandala => "Sacred Mandala".to_string(),
-      ArtStyle::WaveInterference => "Wave Interference".to_string(),
-      ArtStyle::Crystalline => "Crystalline Structure".to_string(),
-    }
+  fn get_art_style(traits: &OrbitalTraits) -> String {
+    PrimaryPatternLayer.variant(traits).to_string()
   }
 
-  fn get_color_palette(index: u128) -> String {
+  fn get_color_palette(traits: &OrbitalTraits) -> String {
     let palettes = [
       "Sunset", "Ocean", "Forest", "Aurora", "Volcanic", "Desert", 
       "Cosmic", "Neon", "Pastel", "Monochrome", "Rainbow", "Earth"
     ];
-    palettes[((index / 6) % 12) as usize].to_string()
+    palettes[traits.color_palette as usize].to_string()
   }
 
-  fn get_pattern_type(index: u128) -> String {
+  fn get_pattern_type(traits: &OrbitalTraits) -> String {
     let patterns = ["Organic", "Geometric", "Hybrid", "Chaotic", "Ordered", "Flowing"];
-    patterns[((index / 72) % 6) as usize].to_string()
+    patterns[traits.pattern_type as usize].to_string()
   }
 
-  fn get_complexity(index: u128) -> String {
-    match (index / 432) % 5 {
+  fn get_complexity(traits: &OrbitalTraits) -> String {
+    match traits.complexity {
       0 => "Minimal".to_string(),
       1 => "Simple".to_string(),
       2 => "Moderate".to_string(),
@@ -103,13 +284,13 @@ impl SvgGenerator {
     }
   }
 
-  fn get_symmetry(index: u128) -> String {
+  fn get_symmetry(traits: &OrbitalTraits) -> String {
     let symmetries = ["Radial", "Bilateral", "Asymmetric", "Rotational"];
-    symmetries[((index / 2160) % 4) as usize].to_string()
+    symmetries[traits.symmetry as usize].to_string()
   }
 
-  fn get_energy_level(index: u128) -> String {
-    match (index / 8640) % 4 {
+  fn get_energy_level(traits: &OrbitalTraits) -> String {
+    match traits.energy_level {
       0 => "Calm".to_string(),
       1 => "Balanced".to_string(),
       2 => "Dynamic".to_string(),
@@ -117,29 +298,33 @@ impl SvgGenerator {
     }
   }
 
-  fn calculate_rarity_score(index: u128) -> u128 {
+  pub fn calculate_rarity_score(traits: &OrbitalTraits) -> u128 {
     // Calculate rarity based on various factors
     let mut score = 0u128;
     
     // Art style rarity
-    score += match Self::get_art_style_enum(index) {
+    score += match Self::get_art_style_enum(traits) {
       ArtStyle::Mandala => 100,
       ArtStyle::Crystalline => 90,
+      ArtStyle::LSystem => 85,
       ArtStyle::WaveInterference => 80,
+      ArtStyle::Voronoi => 75,
       ArtStyle::FlowField => 70,
+      ArtStyle::NoiseField => 65,
       ArtStyle::CirclePacking => 60,
+      ArtStyle::TruchetTiles => 55,
       ArtStyle::GeometricFractal => 50,
     };
     
     // Color palette rarity
-    score += match (index / 6) % 12 {
+    score += match traits.color_palette {
       0 | 11 => 50, // Sunset and Earth are rarer
       1 | 2 => 40,  // Ocean and Forest
       _ => 30,      // Others
     };
     
     // Complexity bonus
-    score += match (index / 432) % 5 {
+    score += match traits.complexity {
       4 => 30, // Intricate
       3 => 20, // Complex
       _ => 10,
@@ -149,27 +334,26 @@ impl SvgGenerator {
   }
 
   // Gradient Generation
-  fn generate_gradients(index: u128) -> String {
-    let palette = Self::get_color_palette_colors(index);
+  fn generate_gradients(traits: &OrbitalTraits, id_suffix: &str) -> String {
+    let palette = Self::get_color_palette_colors(traits);
     format!(r#"
     <defs>
-      <radialGradient id="bg-gradient" cx="50%" cy="50%" r="70%">
+      <radialGradient id="bg-gradient{}" cx="50%" cy="50%" r="70%">
         <stop offset="0%" style="stop-color:{};stop-opacity:0.8"/>
         <stop offset="100%" style="stop-color:{};stop-opacity:1"/>
       </radialGradient>
-      <linearGradient id="pattern-gradient" x1="0%" y1="0%" x2="100%" y2="100%">
+      <linearGradient id="pattern-gradient{}" x1="0%" y1="0%" x2="100%" y2="100%">
         <stop offset="0%" style="stop-color:{}"/>
         <stop offset="50%" style="stop-color:{}"/>
         <stop offset="100%" style="stop-color:{}"/>
       </linearGradient>
     </defs>
-    "#, palette.0, palette.1, palette.2, palette.3, palette.4)
+    "#, id_suffix, palette.0, palette.1, id_suffix, palette.2, palette.3, palette.4)
   }
 
   // Background Generation
-  fn generate_background(index: u128) -> String {
-    format!(r#"<rect width="400" height="400" fill="url(#bg-gradient)"/>{}"#, 
-      Self::generate_background_texture(index))
+  fn generate_background(id_suffix: &str) -> String {
+    format!(r#"<rect width="400" height="400" fill="url(#bg-gradient{})"/>"#, id_suffix)
   }
 
   fn generate_background_texture(index: u128) -> String {
@@ -191,8 +375,8 @@ impl SvgGenerator {
   }
 
   // Color Palette Generation
-  fn get_color_palette_colors(index: u128) -> (String, String, String, String, String) {
-    match (index / 6) % 12 {
+  fn get_color_palette_colors(traits: &OrbitalTraits) -> (String, String, String, String, String) {
+    match traits.color_palette {
       0 => ("hsl(10, 80%, 60%)".to_string(), "hsl(30, 90%, 50%)".to_string(), "hsl(50, 85%, 55%)".to_string(), "hsl(20, 75%, 45%)".to_string(), "hsl(340, 70%, 50%)".to_string()), // Sunset
       1 => ("hsl(200, 80%, 40%)".to_string(), "hsl(220, 90%, 60%)".to_string(), "hsl(180, 85%, 45%)".to_string(), "hsl(240, 70%, 50%)".to_string(), "hsl(160, 75%, 40%)".to_string()), // Ocean
       2 => ("hsl(120, 60%, 30%)".to_string(), "hsl(100, 70%, 40%)".to_string(), "hsl(80, 65%, 45%)".to_string(), "hsl(140, 55%, 35%)".to_string(), "hsl(60, 60%, 50%)".to_string()), // Forest
@@ -209,10 +393,10 @@ impl SvgGenerator {
   }
 
   // Pattern Generators
-  fn generate_fractal_pattern(index: u128) -> String {
+  fn generate_fractal_pattern(index: u128, traits: &OrbitalTraits) -> String {
     let seed = index * 1931; // Prime for good distribution
     let mut pattern = String::new();
-    let colors = Self::get_color_palette_colors(index);
+    let colors = Self::get_color_palette_colors(traits);
     
     // Generate recursive squares
     for depth in 0..6 {
@@ -239,10 +423,10 @@ impl SvgGenerator {
     pattern
   }
 
-  fn generate_flow_field(index: u128) -> String {
+  fn generate_flow_field(index: u128, traits: &OrbitalTraits) -> String {
     let seed = index * 2017;
     let mut pattern = String::new();
-    let colors = Self::get_color_palette_colors(index);
+    let colors = Self::get_color_palette_colors(traits);
     
     // Generate flowing curves
     for i in 0..30 {
@@ -279,10 +463,10 @@ impl SvgGenerator {
     pattern
   }
 
-  fn generate_circle_packing(index: u128) -> String {
+  fn generate_circle_packing(index: u128, traits: &OrbitalTraits) -> String {
     let seed = index * 2099;
     let mut pattern = String::new();
-    let colors = Self::get_color_palette_colors(index);
+    let colors = Self::get_color_palette_colors(traits);
     
     // Generate packed circles
     for i in 0..50 {
@@ -303,44 +487,116 @@ impl SvgGenerator {
     pattern
   }
 
-  fn generate_mandala(index: u128) -> String {
-    let _seed = index * 2111;
+  fn generate_mandala(index: u128, traits: &OrbitalTraits) -> String {
+    let seed = index * 2111;
     let mut pattern = String::new();
-    let colors = Self::get_color_palette_colors(index);
-    
-    // Generate mandala pattern
-    let center_x = 200.0;
-    let center_y = 200.0;
-    
-    for ring in 1..8 {
-      let radius = ring as f64 * 25.0;
-      let points = ring * 8;
-      
-      for i in 0..points {
-        let angle = (i as f64 / points as f64) * 2.0 * std::f64::consts::PI;
-        let x = center_x + angle.cos() * radius;
-        let y = center_y + angle.sin() * radius;
-        let size = 15.0 - ring as f64 * 1.5;
-        
-        let color_idx = (ring + i) % 5;
-        let color = match color_idx {
-          0 => &colors.0, 1 => &colors.1, 2 => &colors.2, 3 => &colors.3, _ => &colors.4
-        };
-        
-        pattern.push_str(&format!(
-          r#"<circle cx="{}" cy="{}" r="{}" fill="{}" opacity="0.8"/>"#,
-          x, y, size, color
-        ));
+    let colors = Self::get_color_palette_colors(traits);
+    let palette = [&colors.0, &colors.1, &colors.2, &colors.3, &colors.4];
+    let pick = |x: i64, y: i64, options: usize| (unit_hash(seed, x, y) * options as f64) as usize % options;
+
+    // Petal count follows the symmetry trait. Radial and rotational mandalas
+    // space petals evenly, rotational ones with a pinwheel tilt; bilateral
+    // ones mirror one half across the vertical axis; asymmetric ones scatter
+    // petals unevenly around each ring
+    let order = match traits.symmetry {
+      0 => [12, 16, 24][pick(0, 0, 3)],
+      1 => [4, 6, 8][pick(0, 0, 3)],
+      2 => [5, 7, 11][pick(0, 0, 3)],
+      _ => [6, 8, 10][pick(0, 0, 3)],
+    };
+    let tilt = if traits.symmetry == 3 { 20.0 + unit_hash(seed, 0, 1) * 25.0 } else { 0.0 };
+
+    let rings = 4 + pick(0, 2, 5) as i64;
+    let spacing = 180.0 / (rings + 1) as f64;
+
+    pattern.push_str(&format!(
+      r#"<circle cx="200" cy="200" r="{:.1}" fill="{}" opacity="0.9"/>"#,
+      spacing * 0.6, palette[pick(0, 3, 5)]
+    ));
+
+    for ring in 1..=rings {
+      let radius = ring as f64 * spacing + unit_hash(seed, ring, 0) * spacing * 0.3;
+      // Outer rings may double the order, which keeps the overall symmetry
+      let petals = order * (1 + pick(ring, 1, 2));
+      let size = (spacing * (0.35 + unit_hash(seed, ring, 2) * 0.35))
+        .min(std::f64::consts::PI * radius / petals as f64 * 1.2);
+      let offset = unit_hash(seed, ring, 3) * 360.0 / petals as f64;
+      let color = palette[pick(ring, 4, 5)];
+      let paint = if pick(ring, 5, 3) == 0 {
+        format!(r#"fill="none" stroke="{}" stroke-width="2""#, color)
+      } else {
+        format!(r#"fill="{}""#, color)
+      };
+
+      let cy = 200.0 - radius;
+      let shape = match pick(ring, 6, 4) {
+        0 => format!(r#"<circle cx="200" cy="{:.1}" r="{:.1}""#, cy, size * 0.6),
+        1 => format!(r#"<ellipse cx="200" cy="{:.1}" rx="{:.1}" ry="{:.1}""#, cy, size * 0.4, size),
+        2 => format!(
+          r#"<path d="M 200 {:.1} C {:.1} {:.1} {:.1} {:.1} 200 {:.1} C {:.1} {:.1} {:.1} {:.1} 200 {:.1} Z""#,
+          cy + size,
+          200.0 + size * 0.8, cy, 200.0 + size * 0.5, cy - size, cy - size,
+          200.0 - size * 0.5, cy - size, 200.0 - size * 0.8, cy, cy + size
+        ),
+        _ => {
+          let sides = 3 + pick(ring, 7, 4);
+          let points = (0..sides)
+            .map(|side| {
+              let angle = side as f64 / sides as f64 * std::f64::consts::TAU;
+              format!("{:.1},{:.1}", 200.0 + angle.sin() * size * 0.7, cy - angle.cos() * size * 0.7)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+          format!(r#"<polygon points="{}""#, points)
+        }
+      };
+
+      let petal = |angle: f64, shift: Option<f64>| {
+        let translate = shift.map(|shift| format!(" translate(0 {:.1})", shift)).unwrap_or_default();
+        format!(
+          r#"{} {} opacity="0.8" transform="rotate({:.2} 200 200){} rotate({:.1} 200 {:.1})"/>"#,
+          shape, paint, angle, translate, tilt, cy
+        )
+      };
+
+      match traits.symmetry {
+        1 => {
+          // Uneven spacing within the right half leaves the reflection as the only symmetry
+          let half = petals / 2;
+          let side = (0..half)
+            .map(|i| {
+              let angle = (i as f64 + 0.15 + unit_hash(seed, ring, 10 + i as i64) * 0.7) * 180.0 / half as f64;
+              petal(angle, None)
+            })
+            .collect::<String>();
+          pattern.push_str(&side);
+          pattern.push_str(&format!(r#"<g transform="matrix(-1 0 0 1 400 0)">{}</g>"#, side));
+        }
+        2 => {
+          for i in 0..petals {
+            if unit_hash(seed, ring, 40 + i as i64) < 0.2 {
+              continue;
+            }
+            let angle = offset + (i as f64 + unit_hash(seed, ring, 70 + i as i64) * 0.8 - 0.4) * 360.0 / petals as f64;
+            let shift = (unit_hash(seed, ring, 100 + i as i64) - 0.5) * spacing * 0.6;
+            pattern.push_str(&petal(angle, Some(shift)));
+          }
+        }
+        _ => {
+          for i in 0..petals {
+            pattern.push_str(&petal(offset + i as f64 * 360.0 / petals as f64, None));
+          }
+        }
       }
     }
-    
+
     pattern
   }
 
-  fn generate_wave_pattern(index: u128) -> String {
+  fn generate_wave_pattern(index: u128, traits: &OrbitalTraits) -> String {
     let seed = index * 2131;
     let mut pattern = String::new();
-    let colors = Self::get_color_palette_colors(index);
+    let colors = Self::get_color_palette_colors(traits);
     
     // Generate wave interference pattern
     for wave in 0..5 {
@@ -369,10 +625,10 @@ impl SvgGenerator {
     pattern
   }
 
-  fn generate_crystal_pattern(index: u128) -> String {
+  fn generate_crystal_pattern(index: u128, traits: &OrbitalTraits) -> String {
     let seed = index * 2141;
     let mut pattern = String::new();
-    let colors = Self::get_color_palette_colors(index);
+    let colors = Self::get_color_palette_colors(traits);
     
     // Generate crystalline structures
     for crystal in 0..12 {
@@ -407,22 +663,322 @@ impl SvgGenerator {
     pattern
   }
 
-  fn generate_overlay_effects(index: u128) -> String {
+  fn generate_voronoi(index: u128, traits: &OrbitalTraits) -> String {
+    let seed = index * 2161;
+    let mut pattern = String::new();
+    let colors = Self::get_color_palette_colors(traits);
+    let colors = [&colors.0, &colors.1, &colors.2, &colors.3, &colors.4];
+
+    // Scatter cell sites
+    let site_count = 10 + traits.complexity as usize * 5;
+    let sites: Vec<(f64, f64)> = (0..site_count)
+      .map(|i| (
+        10.0 + unit_hash(seed, i as i64, 0) * 380.0,
+        10.0 + unit_hash(seed, i as i64, 1) * 380.0,
+      ))
+      .collect();
+
+    for (i, &(px, py)) in sites.iter().enumerate() {
+      // Clip the canvas to the half-planes closer to this site than to any other
+      let mut cell = vec![(0.0, 0.0), (400.0, 0.0), (400.0, 400.0), (0.0, 400.0)];
+      for (j, &(qx, qy)) in sites.iter().enumerate() {
+        if i != j {
+          let limit = (qx * qx + qy * qy - px * px - py * py) / 2.0;
+          cell = Self::clip_polygon(&cell, qx - px, qy - py, limit);
+        }
+      }
+      if cell.len() < 3 {
+        continue;
+      }
+
+      let points = cell.iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ");
+      pattern.push_str(&format!(
+        r#"<polygon points="{}" fill="{}" opacity="0.75" stroke="white" stroke-width="2"/>"#,
+        points, colors[i % 5]
+      ));
+      pattern.push_str(&format!(
+        r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="white" opacity="0.8"/>"#,
+        px, py
+      ));
+    }
+
+    pattern
+  }
+
+  /// Keep the part of a convex polygon where nx * x + ny * y <= limit
+  fn clip_polygon(polygon: &[(f64, f64)], nx: f64, ny: f64, limit: f64) -> Vec<(f64, f64)> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for (k, &current) in polygon.iter().enumerate() {
+      let next = polygon[(k + 1) % polygon.len()];
+      let current_side = nx * current.0 + ny * current.1 - limit;
+      let next_side = nx * next.0 + ny * next.1 - limit;
+
+      if current_side <= 0.0 {
+        clipped.push(current);
+      }
+      if (current_side <= 0.0) != (next_side <= 0.0) {
+        let t = current_side / (current_side - next_side);
+        clipped.push((
+          current.0 + (next.0 - current.0) * t,
+          current.1 + (next.1 - current.1) * t,
+        ));
+      }
+    }
+
+    clipped
+  }
+
+  fn generate_truchet_tiles(index: u128, traits: &OrbitalTraits) -> String {
+    let seed = index * 2179;
+    let mut pattern = String::new();
+    let colors = Self::get_color_palette_colors(traits);
+    let colors = [&colors.0, &colors.1, &colors.2, &colors.3, &colors.4];
+
+    // Quarter-circle tiles, each flipped by the seed
+    let tiles = 6 + traits.complexity as i64 * 2;
+    let size = 400.0 / tiles as f64;
+    let radius = size / 2.0;
+
+    for row in 0..tiles {
+      for column in 0..tiles {
+        let x = column as f64 * size;
+        let y = row as f64 * size;
+        let flipped = unit_hash(seed, column, row) < 0.5;
+        let color = colors[((row + column) % 5) as usize];
+
+        let arcs = if flipped {
+          format!(
+            "M {:.1} {:.1} A {:.1} {:.1} 0 0 0 {:.1} {:.1} M {:.1} {:.1} A {:.1} {:.1} 0 0 0 {:.1} {:.1}",
+            x + radius, y, radius, radius, x + size, y + radius,
+            x, y + radius, radius, radius, x + radius, y + size,
+          )
+        } else {
+          format!(
+            "M {:.1} {:.1} A {:.1} {:.1} 0 0 1 {:.1} {:.1} M {:.1} {:.1} A {:.1} {:.1} 0 0 1 {:.1} {:.1}",
+            x + radius, y, radius, radius, x, y + radius,
+            x + size, y + radius, radius, radius, x + radius, y + size,
+          )
+        };
+
+        pattern.push_str(&format!(
+          r#"<path d="{}" stroke="{}" stroke-width="{:.1}" fill="none" opacity="0.85" stroke-linecap="round"/>"#,
+          arcs, color, size / 6.0
+        ));
+      }
+    }
+
+    pattern
+  }
+
+  fn generate_lsystem(index: u128, traits: &OrbitalTraits) -> String {
+    let seed = index * 2203;
+    let mut pattern = String::new();
+    let colors = Self::get_color_palette_colors(traits);
+    let colors = [&colors.0, &colors.1, &colors.2, &colors.3, &colors.4];
+
+    // Classic branching grammars: (axiom, X rule, F rule)
+    let grammars = [
+      ("F", "", "F[+F]F[-F]F"),
+      ("X", "F[+X][-X]FX", "FF"),
+      ("X", "F-[[X]+X]+F[+FX]-X", "FF"),
+      ("F", "", "FF-[-F+F+F]+[+F-F-F]"),
+    ];
+    let (axiom, x_rule, f_rule) = grammars[(mix64(seed as u64) % grammars.len() as u64) as usize];
+
+    // Rewrite, stopping before the string gets too long to draw
+    let mut program = axiom.to_string();
+    for _ in 0..(3 + traits.complexity / 2) {
+      let mut next = String::with_capacity(program.len() * 4);
+      for symbol in program.chars() {
+        match symbol {
+          'X' if !x_rule.is_empty() => next.push_str(x_rule),
+          'F' => next.push_str(f_rule),
+          other => next.push(other),
+        }
+      }
+      if next.len() > 6000 {
+        break;
+      }
+      program = next;
+    }
+
+    let turn = (18.0 + unit_hash(seed, 0, 0) * 12.0).to_radians();
+
+    // Interpret with a turtle, collecting segments by branch depth
+    let mut segments: Vec<(usize, f64, f64, f64, f64)> = Vec::new();
+    let mut stack = Vec::new();
+    let (mut x, mut y, mut heading, mut depth) = (0.0f64, 0.0f64, -std::f64::consts::FRAC_PI_2, 0usize);
+    for symbol in program.chars() {
+      match symbol {
+        'F' => {
+          let (nx, ny) = (x + heading.cos(), y + heading.sin());
+          segments.push((depth, x, y, nx, ny));
+          x = nx;
+          y = ny;
+        }
+        '+' => heading += turn,
+        '-' => heading -= turn,
+        '[' => {
+          stack.push((x, y, heading, depth));
+          depth += 1;
+        }
+        ']' => {
+          if let Some(state) = stack.pop() {
+            (x, y, heading, depth) = state;
+          }
+        }
+        _ => {}
+      }
+    }
+
+    if segments.is_empty() {
+      return pattern;
+    }
+
+    // Fit the drawing into the canvas
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for &(_, x1, y1, x2, y2) in &segments {
+      min_x = min_x.min(x1).min(x2);
+      min_y = min_y.min(y1).min(y2);
+      max_x = max_x.max(x1).max(x2);
+      max_y = max_y.max(y1).max(y2);
+    }
+    let scale = 320.0 / (max_x - min_x).max(max_y - min_y).max(1.0);
+    let offset_x = 200.0 - (min_x + max_x) / 2.0 * scale;
+    let offset_y = 360.0 - max_y * scale;
+
+    let mut paths = vec![String::new(); 5];
+    for &(depth, x1, y1, x2, y2) in &segments {
+      paths[depth % 5].push_str(&format!(
+        "M {:.1} {:.1} L {:.1} {:.1} ",
+        x1 * scale + offset_x, y1 * scale + offset_y,
+        x2 * scale + offset_x, y2 * scale + offset_y,
+      ));
+    }
+
+    for (depth, path) in paths.iter().enumerate() {
+      if !path.is_empty() {
+        pattern.push_str(&format!(
+          r#"<path d="{}" stroke="{}" stroke-width="{}" fill="none" opacity="0.85" stroke-linecap="round"/>"#,
+          path.trim_end(), colors[depth], (3 - depth.min(2)) as f64
+        ));
+      }
+    }
+
+    pattern
+  }
+
+  fn generate_noise_contours(index: u128, traits: &OrbitalTraits) -> String {
+    let seed = index * 2213;
+    let mut pattern = String::new();
+    let colors = Self::get_color_palette_colors(traits);
+    let colors = [&colors.0, &colors.1, &colors.2, &colors.3, &colors.4];
+
+    // Sample two octaves of value noise on a grid
+    const SAMPLES: usize = 33;
+    let step = 400.0 / (SAMPLES - 1) as f64;
+    let mut field = vec![[0.0f64; SAMPLES]; SAMPLES];
+    for (row, values) in field.iter_mut().enumerate() {
+      for (column, value) in values.iter_mut().enumerate() {
+        let (x, y) = (column as f64 * step, row as f64 * step);
+        *value = Self::value_noise(seed, x / 100.0, y / 100.0) * 0.7
+          + Self::value_noise(seed + 1, x / 50.0, y / 50.0) * 0.3;
+      }
+    }
+
+    // Trace iso-lines with marching squares
+    let levels = 3 + traits.complexity as usize;
+    for level in 0..levels {
+      let threshold = (level + 1) as f64 / (levels + 1) as f64;
+      let mut path = String::new();
+
+      for row in 0..SAMPLES - 1 {
+        for column in 0..SAMPLES - 1 {
+          let corners = [
+            field[row][column],
+            field[row][column + 1],
+            field[row + 1][column + 1],
+            field[row + 1][column],
+          ];
+          let (x, y) = (column as f64 * step, row as f64 * step);
+
+          // Points where the threshold crosses each cell edge: top, right, bottom, left
+          let crossing = |a: usize, b: usize| -> Option<(f64, f64)> {
+            let (va, vb) = (corners[a], corners[b]);
+            if (va < threshold) == (vb < threshold) {
+              return None;
+            }
+            let t = (threshold - va) / (vb - va);
+            let offsets = [(0.0, 0.0), (step, 0.0), (step, step), (0.0, step)];
+            let (ax, ay) = offsets[a];
+            let (bx, by) = offsets[b];
+            Some((x + ax + (bx - ax) * t, y + ay + (by - ay) * t))
+          };
+
+          let points: Vec<(f64, f64)> = [(0, 1), (1, 2), (2, 3), (3, 0)]
+            .iter()
+            .filter_map(|&(a, b)| crossing(a, b))
+            .collect();
+
+          for pair in points.chunks(2) {
+            if let [(x1, y1), (x2, y2)] = pair {
+              path.push_str(&format!("M {:.1} {:.1} L {:.1} {:.1} ", x1, y1, x2, y2));
+            }
+          }
+        }
+      }
+
+      if !path.is_empty() {
+        pattern.push_str(&format!(
+          r#"<path d="{}" stroke="{}" stroke-width="2" fill="none" opacity="0.8" stroke-linejoin="round"/>"#,
+          path.trim_end(), colors[level % 5]
+        ));
+      }
+    }
+
+    pattern
+  }
+
+  /// Smoothly interpolated lattice noise in [0, 1)
+  fn value_noise(seed: u128, x: f64, y: f64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (sx, sy) = (fx * fx * (3.0 - 2.0 * fx), fy * fy * (3.0 - 2.0 * fy));
+    let (ix, iy) = (x0 as i64, y0 as i64);
+
+    let top = unit_hash(seed, ix, iy) * (1.0 - sx) + unit_hash(seed, ix + 1, iy) * sx;
+    let bottom = unit_hash(seed, ix, iy + 1) * (1.0 - sx) + unit_hash(seed, ix + 1, iy + 1) * sx;
+    top * (1.0 - sy) + bottom * sy
+  }
+
+  fn generate_overlay_effects(index: u128, sparkles: u128, options: RenderOptions) -> String {
     let seed = index * 2153;
     let mut overlay = String::new();
     
     // Add some sparkle effects
-    for i in 0..10 {
+    for i in 0..sparkles {
       let x = ((seed * (i + 1) * 91) % 400) as f64;
       let y = ((seed * (i + 1) * 101) % 400) as f64;
       let size = ((seed * (i + 1) * 61) % 3) + 1;
       
-      overlay.push_str(&format!(
-        r#"<circle cx="{}" cy="{}" r="{}" fill="white" opacity="0.8">
+      // Static renders show the sparkles at the brightest frame of their twinkle
+      if options.static_mode {
+        overlay.push_str(&format!(
+          r#"<circle cx="{}" cy="{}" r="{}" fill="white" opacity="0.8"/>"#,
+          x, y, size
+        ));
+      } else {
+        overlay.push_str(&format!(
+          r#"<circle cx="{}" cy="{}" r="{}" fill="white" opacity="0.8">
           <animate attributeName="opacity" values="0.8;0.2;0.8" dur="2s" repeatCount="indefinite"/>
         </circle>"#,
-        x, y, size
-      ));
+          x, y, size
+        ));
+      }
     }
     
     overlay
@@ -437,4 +993,534 @@ enum ArtStyle {
   Mandala,
   WaveInterference,
   Crystalline,
-} 
\ No newline at end of file
+  Voronoi,
+  TruchetTiles,
+  LSystem,
+  NoiseField,
+} 
+
+/// Surface detail drawn by the texture layer, selected by pattern type
+#[derive(Debug, Clone, Copy)]
+enum Texture {
+  Stardust,
+  Grid,
+  Scratches,
+  Ripples,
+}
+
+impl Texture {
+  fn from_traits(traits: &OrbitalTraits) -> Texture {
+    match traits.pattern_type {
+      0 | 2 => Texture::Stardust,
+      1 | 4 => Texture::Grid,
+      3 => Texture::Scratches,
+      _ => Texture::Ripples,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      Texture::Stardust => "Stardust",
+      Texture::Grid => "Grid",
+      Texture::Scratches => "Scratches",
+      Texture::Ripples => "Ripples",
+    }
+  }
+}
+
+/// Motif drawn by the accent layer, selected by symmetry
+#[derive(Debug, Clone, Copy)]
+enum Accent {
+  HaloRings,
+  MirroredArcs,
+  OffsetOrb,
+  Spokes,
+}
+
+impl Accent {
+  fn from_traits(traits: &OrbitalTraits) -> Accent {
+    match traits.symmetry {
+      0 => Accent::HaloRings,
+      1 => Accent::MirroredArcs,
+      2 => Accent::OffsetOrb,
+      _ => Accent::Spokes,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      Accent::HaloRings => "Halo Rings",
+      Accent::MirroredArcs => "Mirrored Arcs",
+      Accent::OffsetOrb => "Offset Orb",
+      Accent::Spokes => "Spokes",
+    }
+  }
+}
+
+/// Border drawn by the frame layer, selected by complexity
+#[derive(Debug, Clone, Copy)]
+enum Frame {
+  None,
+  ThinBorder,
+  DoubleBorder,
+  CornerBrackets,
+  Ornate,
+}
+
+impl Frame {
+  fn from_traits(traits: &OrbitalTraits) -> Frame {
+    match traits.complexity {
+      0 => Frame::None,
+      1 => Frame::ThinBorder,
+      2 => Frame::DoubleBorder,
+      3 => Frame::CornerBrackets,
+      _ => Frame::Ornate,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      Frame::None => "None",
+      Frame::ThinBorder => "Thin Border",
+      Frame::DoubleBorder => "Double Border",
+      Frame::CornerBrackets => "Corner Brackets",
+      Frame::Ornate => "Ornate",
+    }
+  }
+}
+
+/// Effect drawn by the overlay layer, selected by energy level
+#[derive(Debug, Clone, Copy)]
+enum Overlay {
+  Sparkles,
+  TwinkleField,
+  SparkleStorm,
+  Starburst,
+}
+
+impl Overlay {
+  fn from_traits(traits: &OrbitalTraits) -> Overlay {
+    match traits.energy_level {
+      0 => Overlay::Sparkles,
+      1 => Overlay::TwinkleField,
+      2 => Overlay::SparkleStorm,
+      _ => Overlay::Starburst,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      Overlay::Sparkles => "Sparkles",
+      Overlay::TwinkleField => "Twinkle Field",
+      Overlay::SparkleStorm => "Sparkle Storm",
+      Overlay::Starburst => "Starburst",
+    }
+  }
+
+  fn sparkle_count(&self) -> u128 {
+    match self {
+      Overlay::Sparkles => 10,
+      Overlay::TwinkleField => 16,
+      Overlay::SparkleStorm | Overlay::Starburst => 24,
+    }
+  }
+}
+
+/// Inputs shared by every layer of a render
+pub struct RenderContext<'a> {
+  pub index: u128,
+  pub traits: &'a OrbitalTraits,
+  /// Appended to SVG ids so several renders can share one document
+  pub id_suffix: &'a str,
+  pub options: RenderOptions,
+}
+
+/// One stage of the art pipeline: draws its part of the SVG and names the
+/// variant it draws for the attributes. Each layer's variant comes from a
+/// single trait.
+pub trait Layer {
+  /// Name listed in the attributes
+  fn name(&self) -> &'static str;
+  fn variant(&self, traits: &OrbitalTraits) -> &'static str;
+  fn render(&self, context: &RenderContext) -> String;
+}
+
+/// Layers of a full orbital render, bottom to top
+const ORBITAL_LAYERS: &[&dyn Layer] = &[
+  &BackgroundLayer,
+  &TextureLayer,
+  &PrimaryPatternLayer,
+  &AccentLayer,
+  &FrameLayer,
+  &OverlayLayer,
+  &SignatureLayer,
+];
+
+/// Layers of each bundle tile; the bundle draws its own labels and skips animation
+const BUNDLE_TILE_LAYERS: &[&dyn Layer] = &[
+  &BackgroundLayer,
+  &TextureLayer,
+  &PrimaryPatternLayer,
+  &AccentLayer,
+  &FrameLayer,
+];
+
+/// Palette gradient fill, selected by color palette
+struct BackgroundLayer;
+
+impl Layer for BackgroundLayer {
+  fn name(&self) -> &'static str {
+    "background"
+  }
+
+  fn variant(&self, traits: &OrbitalTraits) -> &'static str {
+    match traits.color_palette {
+      0 => "Sunset Glow",
+      1 => "Ocean Glow",
+      2 => "Forest Glow",
+      3 => "Aurora Glow",
+      4 => "Volcanic Glow",
+      5 => "Desert Glow",
+      6 => "Cosmic Glow",
+      7 => "Neon Glow",
+      8 => "Pastel Glow",
+      9 => "Monochrome Glow",
+      10 => "Rainbow Glow",
+      _ => "Earth Glow",
+    }
+  }
+
+  fn render(&self, context: &RenderContext) -> String {
+    let mut layer = SvgGenerator::generate_gradients(context.traits, context.id_suffix);
+    layer.push_str(&SvgGenerator::generate_background(context.id_suffix));
+    layer
+  }
+}
+
+/// Faint surface detail behind the pattern, selected by pattern type
+struct TextureLayer;
+
+impl Layer for TextureLayer {
+  fn name(&self) -> &'static str {
+    "texture"
+  }
+
+  fn variant(&self, traits: &OrbitalTraits) -> &'static str {
+    Texture::from_traits(traits).name()
+  }
+
+  fn render(&self, context: &RenderContext) -> String {
+    let seed = context.index * 4049;
+    let mut texture = String::new();
+
+    match Texture::from_traits(context.traits) {
+      Texture::Grid => {
+        for i in 1..16 {
+          let offset = i * 25;
+          texture.push_str(&format!(
+            r#"<line x1="{0}" y1="0" x2="{0}" y2="400" stroke="white" stroke-width="0.5" opacity="0.06"/><line x1="0" y1="{0}" x2="400" y2="{0}" stroke="white" stroke-width="0.5" opacity="0.06"/>"#,
+            offset
+          ));
+        }
+      }
+      Texture::Scratches => {
+        for i in 0..16 {
+          let x = unit_hash(seed, i, 0) * 400.0;
+          let y = unit_hash(seed, i, 1) * 400.0;
+          let angle = unit_hash(seed, i, 2) * std::f64::consts::TAU;
+          let length = 20.0 + unit_hash(seed, i, 3) * 60.0;
+          texture.push_str(&format!(
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="white" stroke-width="1" opacity="0.08"/>"#,
+            x, y, x + angle.cos() * length, y + angle.sin() * length
+          ));
+        }
+      }
+      Texture::Ripples => {
+        let cx = 100.0 + unit_hash(seed, 0, 0) * 200.0;
+        let cy = 100.0 + unit_hash(seed, 0, 1) * 200.0;
+        for ring in 1..8 {
+          texture.push_str(&format!(
+            r#"<circle cx="{:.1}" cy="{:.1}" r="{}" fill="none" stroke="white" stroke-width="1" opacity="0.07"/>"#,
+            cx, cy, ring * 40
+          ));
+        }
+      }
+      Texture::Stardust => texture.push_str(&SvgGenerator::generate_background_texture(context.index)),
+    }
+
+    texture
+  }
+}
+
+/// The orbital's main generative artwork, selected by art style
+struct PrimaryPatternLayer;
+
+impl Layer for PrimaryPatternLayer {
+  fn name(&self) -> &'static str {
+    "primary_pattern"
+  }
+
+  fn variant(&self, traits: &OrbitalTraits) -> &'static str {
+    match SvgGenerator::get_art_style_enum(traits) {
+      ArtStyle::GeometricFractal => "Geometric Fractal",
+      ArtStyle::FlowField => "Flow Field",
+      ArtStyle::CirclePacking => "Circle Packing",
+      ArtStyle::Mandala => "Sacred Mandala",
+      ArtStyle::WaveInterference => "Wave Interference",
+      ArtStyle::Crystalline => "Crystalline Structure",
+      ArtStyle::Voronoi => "Voronoi Mosaic",
+      ArtStyle::TruchetTiles => "Truchet Tiles",
+      ArtStyle::LSystem => "L-System Growth",
+      ArtStyle::NoiseField => "Noise Contours",
+    }
+  }
+
+  fn render(&self, context: &RenderContext) -> String {
+    SvgGenerator::generate_pattern(context.index, context.traits)
+  }
+}
+
+/// Secondary motif drawn over the pattern, selected by symmetry
+struct AccentLayer;
+
+impl Layer for AccentLayer {
+  fn name(&self) -> &'static str {
+    "secondary_accent"
+  }
+
+  fn variant(&self, traits: &OrbitalTraits) -> &'static str {
+    Accent::from_traits(traits).name()
+  }
+
+  fn render(&self, context: &RenderContext) -> String {
+    let seed = context.index * 3571;
+    let colors = SvgGenerator::get_color_palette_colors(context.traits);
+    let mut accent = String::new();
+
+    match Accent::from_traits(context.traits) {
+      Accent::HaloRings => {
+        for ring in 1..4 {
+          accent.push_str(&format!(
+            r#"<circle cx="200" cy="200" r="{}" fill="none" stroke="{}" stroke-width="1.5" stroke-dasharray="4 6" opacity="0.35"/>"#,
+            ring * 55, colors.4
+          ));
+        }
+      }
+      Accent::MirroredArcs => {
+        for arc in 0..3 {
+          let reach = 60.0 + arc as f64 * 40.0 + unit_hash(seed, arc, 0) * 20.0;
+          let top = 80.0 + arc as f64 * 30.0;
+          accent.push_str(&format!(
+            r#"<path d="M {1:.1} {0:.1} Q {2:.1} 200 {1:.1} {3:.1} M {4:.1} {0:.1} Q {5:.1} 200 {4:.1} {3:.1}" fill="none" stroke="{6}" stroke-width="2" opacity="0.4"/>"#,
+            top, 200.0 - reach, 200.0 - reach * 1.6, 400.0 - top, 200.0 + reach, 200.0 + reach * 1.6, colors.4
+          ));
+        }
+      }
+      Accent::OffsetOrb => {
+        let cx = 60.0 + unit_hash(seed, 0, 0) * 280.0;
+        let cy = 60.0 + unit_hash(seed, 0, 1) * 280.0;
+        let r = 30.0 + unit_hash(seed, 0, 2) * 30.0;
+        accent.push_str(&format!(
+          r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="{}" opacity="0.3"/><circle cx="{:.1}" cy="{:.1}" r="{:.1}" fill="none" stroke="white" stroke-width="1" opacity="0.4"/>"#,
+          cx, cy, r, colors.2, cx, cy, r * 1.3
+        ));
+      }
+      Accent::Spokes => {
+        let offset = unit_hash(seed, 0, 0) * 30.0;
+        for spoke in 0..12 {
+          let angle = (spoke as f64 * 30.0 + offset).to_radians();
+          accent.push_str(&format!(
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="1" opacity="0.3"/>"#,
+            200.0 + angle.cos() * 40.0, 200.0 + angle.sin() * 40.0,
+            200.0 + angle.cos() * 190.0, 200.0 + angle.sin() * 190.0,
+            colors.4
+          ));
+        }
+      }
+    }
+
+    accent
+  }
+}
+
+/// Border around the canvas, selected by complexity
+struct FrameLayer;
+
+impl Layer for FrameLayer {
+  fn name(&self) -> &'static str {
+    "frame"
+  }
+
+  fn variant(&self, traits: &OrbitalTraits) -> &'static str {
+    Frame::from_traits(traits).name()
+  }
+
+  fn render(&self, context: &RenderContext) -> String {
+    let colors = SvgGenerator::get_color_palette_colors(context.traits);
+    let border = |inset: u32, width: f64| format!(
+      r#"<rect x="{0}" y="{0}" width="{1}" height="{1}" fill="none" stroke="{2}" stroke-width="{3}" opacity="0.7"/>"#,
+      inset, 400 - inset * 2, colors.2, width
+    );
+    let brackets = || {
+      [(8, 8, 1, 1), (392, 8, -1, 1), (8, 392, 1, -1), (392, 392, -1, -1)].iter()
+        .map(|(x, y, dx, dy)| format!(
+          r#"<path d="M {} {} L {} {} L {} {}" fill="none" stroke="{}" stroke-width="3" opacity="0.8"/>"#,
+          x, y + dy * 40, x, y, x + dx * 40, y, colors.4
+        ))
+        .collect::<String>()
+    };
+
+    match Frame::from_traits(context.traits) {
+      Frame::None => String::new(),
+      Frame::ThinBorder => border(6, 1.5),
+      Frame::DoubleBorder => border(6, 1.5) + &border(12, 0.75),
+      Frame::CornerBrackets => brackets(),
+      Frame::Ornate => {
+        let mut frame = border(6, 1.5) + &border(12, 0.75) + &brackets();
+        for (x, y) in [(8, 8), (392, 8), (8, 392), (392, 392)] {
+          frame.push_str(&format!(r#"<circle cx="{}" cy="{}" r="5" fill="{}" opacity="0.9"/>"#, x, y, colors.4));
+        }
+        frame
+      }
+    }
+  }
+}
+
+/// Sparkles, animated unless rendering statically, selected by energy level
+struct OverlayLayer;
+
+impl Layer for OverlayLayer {
+  fn name(&self) -> &'static str {
+    "overlay"
+  }
+
+  fn variant(&self, traits: &OrbitalTraits) -> &'static str {
+    Overlay::from_traits(traits).name()
+  }
+
+  fn render(&self, context: &RenderContext) -> String {
+    let variant = Overlay::from_traits(context.traits);
+    let mut overlay = SvgGenerator::generate_overlay_effects(context.index, variant.sparkle_count(), context.options);
+
+    if let Overlay::Starburst = variant {
+      for ray in 0..16 {
+        let angle = (ray as f64 * 22.5).to_radians();
+        let length = if ray % 2 == 0 { 190.0 } else { 120.0 };
+        overlay.push_str(&format!(
+          r#"<line x1="200" y1="200" x2="{:.1}" y2="{:.1}" stroke="white" stroke-width="1" opacity="0.25"/>"#,
+          200.0 + angle.cos() * length, 200.0 + angle.sin() * length
+        ));
+      }
+    }
+
+    overlay
+  }
+}
+
+/// Index number in the corner
+struct SignatureLayer;
+
+impl Layer for SignatureLayer {
+  fn name(&self) -> &'static str {
+    "signature"
+  }
+
+  fn variant(&self, _traits: &OrbitalTraits) -> &'static str {
+    "Index"
+  }
+
+  fn render(&self, context: &RenderContext) -> String {
+    format!("<text x=\"20\" y=\"380\" font-family=\"monospace\" font-size=\"12\" fill=\"white\" opacity=\"0.6\">#{}</text>\n", context.index)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn default_weights_keep_index_rotation() {
+    let weights = StyleWeights::default();
+    assert!((0..60).all(|index| weights.pick(index) == index % 6));
+  }
+
+  #[test]
+  fn zero_weight_styles_are_never_picked() {
+    let weights = StyleWeights([0, 0, 3, 0, 0, 0, 0, 0, 0, 1]);
+    assert!((0..500).all(|index| matches!(weights.pick(index), 2 | 9)));
+  }
+
+  #[test]
+  fn styles_are_picked_in_proportion_to_weight() {
+    let weights = StyleWeights([0, 0, 3, 0, 0, 0, 0, 0, 0, 1]);
+
+    let heavy = (0..4000).filter(|index| weights.pick(*index) == 2).count();
+
+    assert!((2700..3300).contains(&heavy), "{} of 4000 picked the 3x style", heavy);
+  }
+
+  #[test]
+  fn layer_variants_name_the_drawn_option() {
+    let mut traits = OrbitalTraits::from_index(5, &StyleWeights::default());
+    traits.pattern_type = 3;
+    traits.symmetry = 2;
+    traits.complexity = 4;
+    traits.energy_level = 3;
+
+    assert_eq!(TextureLayer.variant(&traits), "Scratches");
+    assert_eq!(AccentLayer.variant(&traits), "Offset Orb");
+    assert_eq!(FrameLayer.variant(&traits), "Ornate");
+    assert_eq!(OverlayLayer.variant(&traits), "Starburst");
+  }
+
+  #[test]
+  fn only_starburst_overlay_draws_rays() {
+    let mut traits = OrbitalTraits::from_index(5, &StyleWeights::default());
+    let render = |traits: &OrbitalTraits| OverlayLayer.render(&RenderContext {
+      index: 5,
+      traits,
+      id_suffix: "",
+      options: RenderOptions::default(),
+    });
+
+    traits.energy_level = 2;
+    assert!(!render(&traits).contains(r#"x1="200" y1="200""#));
+    traits.energy_level = 3;
+    assert!(render(&traits).contains(r#"x1="200" y1="200""#));
+  }
+
+  fn mandala(index: u128, symmetry: u128) -> String {
+    let mut traits = OrbitalTraits::from_index(index, &StyleWeights::default());
+    traits.art_style = 3;
+    traits.color_palette = 4;
+    traits.symmetry = symmetry;
+    SvgGenerator::generate_mandala(index, &traits)
+  }
+
+  #[test]
+  fn mandalas_with_the_same_palette_differ_by_index() {
+    for symmetry in 0..4 {
+      assert_ne!(mandala(3, symmetry), mandala(9, symmetry));
+    }
+  }
+
+  #[test]
+  fn only_bilateral_mandalas_are_mirrored() {
+    let mirror = "matrix(-1 0 0 1 400 0)";
+
+    assert!(mandala(15, 1).contains(mirror));
+    assert!([0, 2, 3].iter().all(|symmetry| !mandala(15, *symmetry).contains(mirror)));
+  }
+
+  #[test]
+  fn only_asymmetric_mandalas_shift_petals_off_their_ring() {
+    assert!(mandala(15, 2).contains("translate(0 "));
+    assert!([0, 1, 3].iter().all(|symmetry| !mandala(15, *symmetry).contains("translate(0 ")));
+  }
+
+  #[test]
+  fn index_traits_follow_the_weights() {
+    let weights = StyleWeights([0, 0, 0, 0, 0, 0, 0, 5, 0, 0]);
+    assert!((0..100).all(|index| OrbitalTraits::from_index(index, &weights).art_style == 7));
+  }
+}
@@ -0,0 +1,97 @@
+use metashrew_support::index_pointer::KeyValuePointer;
+
+use alkanes_runtime::{runtime::AlkaneResponder, storage::StoragePointer};
+use alkanes_support::{id::AlkaneId, response::CallResponse};
+
+use anyhow::{anyhow, Result};
+
+use crate::refund::GuaranteeState;
+use crate::{alkane_id_to_bytes, PaymentToken, RoyaltyNFTCollection};
+
+impl RoyaltyNFTCollection {
+  fn treasury_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/treasury/").select(&alkane_id_to_bytes(token))
+  }
+
+  fn treasury_seeded_pointer(&self, token: &AlkaneId) -> StoragePointer {
+    StoragePointer::from_keyword("/treasury-seeded/").select(&alkane_id_to_bytes(token))
+  }
+
+  /// Amount of `token` held by the collection on behalf of the treasury
+  pub(crate) fn treasury_balance(&self, token: &AlkaneId) -> u128 {
+    self.treasury_pointer(token).get_value::<u128>()
+  }
+
+  pub(crate) fn credit_treasury(&self, token: &AlkaneId, amount: u128) -> Result<()> {
+    let balance = self.treasury_balance(token)
+      .checked_add(amount)
+      .ok_or_else(|| anyhow!("treasury balance overflow"))?;
+
+    self.treasury_pointer(token).set_value::<u128>(balance);
+    Ok(())
+  }
+
+  pub(crate) fn debit_treasury(&self, token: &AlkaneId, amount: u128) -> Result<()> {
+    let balance = self.treasury_balance(token);
+    if balance < amount {
+      return Err(anyhow!(
+        "Insufficient treasury balance: {} requested, {} available",
+        amount, balance
+      ));
+    }
+
+    self.treasury_pointer(token).set_value::<u128>(balance - amount);
+    Ok(())
+  }
+
+  pub(crate) fn get_treasury_balance(&self, token_block: u128, token_tx: u128) -> Result<CallResponse> {
+    let context = self.context()?;
+    let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+    let token = AlkaneId { block: token_block, tx: token_tx };
+    response.data = self.treasury_balance(&token).to_le_bytes().to_vec();
+
+    Ok(response)
+  }
+
+  /// Whether any ledger other than the treasury may hold `token` for someone
+  /// else: custody receipts (listings, offers, sales, bids, stakes, referrer
+  /// keys), vault payouts, airdrop deposits, refund escrow, burn payouts,
+  /// staking rewards, referral rewards or unclaimed proceeds
+  fn other_ledgers_hold(&self, token: &AlkaneId) -> bool {
+    self.receipt_count() > 0
+      || self.vault_count() > 0
+      || self.airdrop_count() > 0
+      || self.guarantee_state() != GuaranteeState::None
+      || self.burn_pool(token) > 0
+      || self.staking_pool(token) > 0
+      || self.referrer_count() > 0
+      || self.outstanding_proceeds(token) > 0
+  }
+
+  /// Credit the payment token balance the collection held before the treasury
+  /// ledger existed, so it can be withdrawn. Allowed once per token, and only
+  /// before any other ledger holds funds, since everything held beyond the
+  /// current treasury balance is credited.
+  /// Parameters: [token_type (0=frBTC, 1=BUSD)]
+  pub(crate) fn seed_treasury(&self, token_type: u128) -> Result<CallResponse> {
+    self.only_owner()?;
+
+    let context = self.context()?;
+    let response = CallResponse::forward(&context.incoming_alkanes);
+
+    let token = PaymentToken::from_token_type(token_type)?.get_id();
+    if self.treasury_seeded_pointer(&token).get_value::<u8>() != 0 {
+      return Err(anyhow!("Treasury was already seeded for this token"));
+    }
+    if self.other_ledgers_hold(&token) {
+      return Err(anyhow!("Treasury can only be seeded before escrow, custody or reward ledgers hold funds"));
+    }
+    self.treasury_seeded_pointer(&token).set_value::<u8>(1);
+
+    let held = self.balance(&context.myself, &token);
+    self.credit_treasury(&token, held.saturating_sub(self.treasury_balance(&token)))?;
+
+    Ok(response)
+  }
+}